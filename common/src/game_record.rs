@@ -0,0 +1,542 @@
+//A whole game written down for people to read and share, along the lines of
+//PGN for chess. A record looks like this:
+//
+//    [Game "Canadian Fish"]
+//    [Seed "7"]
+//    [Rules "standard"]
+//    [You "You"]
+//    [TeammateOne "Tia"]
+//    [Result "*"]
+//
+//    You: 2♣ 4♣ 2♦ 6♦ 4♥ 6♥ 3♠ 6♠
+//    TeammateOne: 7♣ Q♣ K♣ 7♦ Q♦ K♦ 2♠ Q♠
+//    ...
+//
+//    You to play
+//    You asks OpponentOne for 5♣: yes
+//    You asks OpponentZero for 3♣: no
+//    OpponentZero to play
+//    You declares 2-7 of ♣: 2♣ You, 3♣ TeammateTwo, 4♣ You, 5♣ You, 6♣ You, 7♣ TeammateOne (OpponentOne): wrong
+//
+//Seats are always written the same way, with the names used in that game
+//given in tags. The tags come first, then the cards each seat was dealt,
+//then every move in order. In a declaration a seat in brackets is who
+//actually had the card, when that isn't who it was given to. The outcome is
+//`correct`, `wrong`, or `cancelled` for a wrong declaration that scored for
+//nobody. `Result` is the score as `us:them`, or `*` for an unfinished game.
+//
+//Suits can also be typed as the letters C, D, H and S. Blank lines, lines
+//starting with `;` and tags we don't know are skipped.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use *;
+use HistoryEntry::*;
+
+pub struct GameRecord {
+    pub seed: Option<usize>,
+    pub rules: RulesVariant,
+    pub names: HashMap<Player, String>,
+    pub deal: HashMap<Player, Hand>,
+    pub moves: Vec<HistoryEntry>,
+}
+
+impl GameRecord {
+    //the points for the player's team and for the other team
+    pub fn score(&self) -> (u8, u8) {
+        self.moves
+            .iter()
+            .fold((0, 0), |(us, them), entry| match *entry {
+                Declared(record) if !record.cancelled => {
+                    if record.scored_for_player_team() {
+                        (us + 1, them)
+                    } else {
+                        (us, them + 1)
+                    }
+                }
+                _ => (us, them),
+            })
+    }
+
+    pub fn finished(&self) -> bool {
+        self.moves
+            .iter()
+            .filter(|entry| matches!(**entry, Declared(_)))
+            .count() == SubSuit::all_values().len()
+    }
+}
+
+pub fn write(record: &GameRecord) -> String {
+    let mut result = String::new();
+
+    result.push_str("[Game \"Canadian Fish\"]\n");
+    if let Some(seed) = record.seed {
+        result.push_str(&format!("[Seed \"{}\"]\n", seed));
+    }
+    result.push_str(&format!("[Rules \"{}\"]\n", rules_word(record.rules)));
+    for seat in ALL_SEATS.iter() {
+        if let Some(name) = record.names.get(seat) {
+            result.push_str(&format!("[{} \"{}\"]\n", seat, name));
+        }
+    }
+    result.push_str(&format!("[Result \"{}\"]\n",
+                             if record.finished() {
+                                 let (us, them) = record.score();
+                                 format!("{}:{}", us, them)
+                             } else {
+                                 "*".to_string()
+                             }));
+
+    result.push('\n');
+    for seat in ALL_SEATS.iter() {
+        let cards: Vec<String> = record
+            .deal
+            .get(seat)
+            .map(|hand| hand.iter().map(|card| card_text(card.suit, card.value)).collect())
+            .unwrap_or_default();
+
+        result.push_str(&format!("{}: {}\n", seat, cards.join(" ")));
+    }
+
+    result.push('\n');
+    for entry in record.moves.iter() {
+        result.push_str(&move_text(entry));
+        result.push('\n');
+    }
+
+    result
+}
+
+fn records_dir(config_dir: &Path) -> PathBuf {
+    config_dir.join("records")
+}
+
+//records go in their own folder next to the settings, named so they sort
+//by when they were saved
+pub fn save(config_dir: Option<&Path>, record: &GameRecord) -> Result<PathBuf, String> {
+    let dir = config_dir
+        .map(records_dir)
+        .ok_or_else(|| "Could not find a config directory to save the record to".to_string())?;
+
+    fs::create_dir_all(&dir)
+        .map_err(|error| format!("Could not create {}: {}", dir.display(), error))?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let path = dir.join(format!("{}-{}.txt",
+                                timestamp,
+                                record.seed.map(|seed| seed.to_string()).unwrap_or_default()));
+
+    File::create(&path)
+        .and_then(|mut file| file.write_all(write(record).as_bytes()))
+        .map_err(|error| format!("Could not write {}: {}", path.display(), error))?;
+
+    Ok(path)
+}
+
+//the records in the records folder, newest first
+pub fn saved_records(config_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = config_dir
+        .and_then(|dir| fs::read_dir(records_dir(dir)).ok())
+        .map(|entries| {
+                 entries
+                     .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                     .filter(|path| path.extension().map(|e| e == "txt").unwrap_or(false))
+                     .collect()
+             })
+        .unwrap_or_default();
+
+    paths.sort_by(|a, b| b.cmp(a));
+
+    paths
+}
+
+pub fn load(path: &Path) -> Result<GameRecord, String> {
+    let mut text = String::new();
+    File::open(path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+
+    parse(&text).map_err(|problem| format!("{}: {}", path.display(), problem))
+}
+
+fn move_text(entry: &HistoryEntry) -> String {
+    match *entry {
+        Asked(ask_vector, suit, value, has_card) => {
+            let (asker, target) = ask_vector_players(ask_vector);
+
+            format!("{} asks {} for {}: {}",
+                    asker,
+                    target,
+                    card_text(suit, value),
+                    if has_card { "yes" } else { "no" })
+        }
+        TurnPassed(player) => format!("{} to play", player),
+        Declared(record) => {
+            let cards: Vec<String> = pairs_from_subsuit(record.subsuit)
+                .iter()
+                .enumerate()
+                .map(|(i, &(suit, value))| if record.claimed[i] == record.actual[i] {
+                         format!("{} {}", card_text(suit, value), record.claimed[i])
+                     } else {
+                         format!("{} {} ({})",
+                                 card_text(suit, value),
+                                 record.claimed[i],
+                                 record.actual[i])
+                     })
+                .collect();
+
+            format!("{} declares {}: {}: {}",
+                    record.declarer,
+                    record.subsuit,
+                    cards.join(", "),
+                    if record.correct {
+                        "correct"
+                    } else if record.cancelled {
+                        "cancelled"
+                    } else {
+                        "wrong"
+                    })
+        }
+    }
+}
+
+fn card_text(suit: Suit, value: Value) -> String {
+    format!("{}{}", value, suit)
+}
+
+fn rules_word(rules: RulesVariant) -> &'static str {
+    match rules {
+        RulesVariant::Standard => "standard",
+        RulesVariant::MisassignedCancelled => "misassigned_cancelled",
+    }
+}
+
+pub fn parse(text: &str) -> Result<GameRecord, String> {
+    let mut record = GameRecord {
+        seed: None,
+        rules: RulesVariant::Standard,
+        names: HashMap::new(),
+        deal: HashMap::new(),
+        moves: Vec::new(),
+    };
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        parse_line(&mut record, line).map_err(|problem| format!("line {}: {}", i + 1, problem))?;
+    }
+
+    for seat in ALL_SEATS.iter() {
+        if !record.deal.contains_key(seat) {
+            return Err(format!("the cards {} was dealt are missing", seat));
+        }
+    }
+
+    let mut seen: Vec<(Suit, Value)> = Vec::new();
+    for hand in record.deal.values() {
+        for card in hand.iter() {
+            if seen.contains(&(card.suit, card.value)) {
+                return Err(format!("{} was dealt more than once", card_text(card.suit, card.value)));
+            }
+            seen.push((card.suit, card.value));
+        }
+    }
+
+    Ok(record)
+}
+
+fn parse_line(record: &mut GameRecord, line: &str) -> Result<(), String> {
+    if line.starts_with('[') {
+        return parse_tag(record, line);
+    }
+
+    if let Some(index) = line.find(": ").or_else(|| if line.ends_with(':') {
+                                                      Some(line.len() - 1)
+                                                  } else {
+                                                      None
+                                                  }) {
+        if let Some(seat) = parse_seat(&line[..index]) {
+            if !record.moves.is_empty() {
+                return Err("the deal has to come before the moves".to_string());
+            }
+
+            let hand = line[index + 1..]
+                .split_whitespace()
+                .map(|word| parse_card(word).map(|(suit, value)| Card { suit, value }))
+                .collect::<Result<Hand, String>>()?;
+            record.deal.insert(seat, hand);
+
+            return Ok(());
+        }
+    }
+
+    let entry = parse_move(line)?;
+    record.moves.push(entry);
+
+    Ok(())
+}
+
+fn parse_tag(record: &mut GameRecord, line: &str) -> Result<(), String> {
+    let inner = line.trim_start_matches('[').trim_end_matches(']');
+    let (name, value) = match inner.find(' ') {
+        Some(index) => (&inner[..index], inner[index..].trim()),
+        None => return Err(format!("expected `[Name \"value\"]` but found `{}`", line)),
+    };
+
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return Err(format!("expected `[Name \"value\"]` but found `{}`", line));
+    }
+    let value = &value[1..value.len() - 1];
+
+    match name {
+        "Seed" => {
+            record.seed = Some(value.parse()
+                                   .map_err(|_| format!("`{}` is not a seed", value))?);
+        }
+        "Rules" => {
+            record.rules = match value {
+                "standard" => RulesVariant::Standard,
+                "misassigned_cancelled" => RulesVariant::MisassignedCancelled,
+                _ => {
+                    return Err(format!("expected standard or misassigned_cancelled but found \
+                                        `{}`",
+                                       value))
+                }
+            };
+        }
+        _ => {
+            if let Some(seat) = parse_seat(name) {
+                record.names.insert(seat, value.to_string());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_move(line: &str) -> Result<HistoryEntry, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    match words.as_slice() {
+        [player, "to", "play"] => Ok(TurnPassed(expect_seat(player)?)),
+        [asker, "asks", target, "for", card, answer] => {
+            let (asker, target) = (expect_seat(asker)?, expect_seat(target)?);
+            let (suit, value) = parse_card(card.trim_end_matches(':'))?;
+            let has_card = match *answer {
+                "yes" => true,
+                "no" => false,
+                _ => return Err(format!("expected yes or no but found `{}`", answer)),
+            };
+
+            let ask_vector = match (asker, target) {
+                (TeammatePlayer(asker), OpponentPlayer(target)) => ToOpponent(asker, target),
+                (OpponentPlayer(asker), TeammatePlayer(target)) => ToTeammate(asker, target),
+                _ => return Err(format!("{} and {} are on the same team", asker, target)),
+            };
+
+            Ok(Asked(ask_vector, suit, value, has_card))
+        }
+        _ if words.get(1) == Some(&"declares") => parse_declaration(line),
+        _ => Err(format!("`{}` is not a move", line)),
+    }
+}
+
+fn parse_declaration(line: &str) -> Result<HistoryEntry, String> {
+    let parts: Vec<&str> = line.split(": ").collect();
+    let (heading, cards, outcome) = match parts.as_slice() {
+        [heading, cards, outcome] => (heading, cards, outcome.trim()),
+        _ => {
+            return Err(format!("expected `Seat declares half-suit: cards: outcome` but found \
+                                `{}`",
+                               line))
+        }
+    };
+
+    let declarer_text = heading.split_whitespace().next().unwrap_or("");
+    let declarer = expect_seat(declarer_text)?;
+    let subsuit_text = heading[declarer_text.len()..].trim().trim_start_matches("declares").trim();
+    let subsuit = SubSuit::all_values()
+        .into_iter()
+        .find(|subsuit| subsuit.to_string() == normalize_suits(subsuit_text))
+        .ok_or_else(|| format!("`{}` is not a half-suit", subsuit_text))?;
+
+    let pairs = pairs_from_subsuit(subsuit);
+    let mut claimed = [declarer; 6];
+    let mut actual = [declarer; 6];
+    let mut found = [false; 6];
+
+    for card in cards.split(',') {
+        let words: Vec<&str> = card.split_whitespace().collect();
+        let (pair, claimed_seat, actual_seat) = match words.as_slice() {
+            [card, seat] => (parse_card(card)?, expect_seat(seat)?, expect_seat(seat)?),
+            [card, seat, holder] if holder.starts_with('(') && holder.ends_with(')') => {
+                (parse_card(card)?,
+                 expect_seat(seat)?,
+                 expect_seat(&holder[1..holder.len() - 1])?)
+            }
+            _ => return Err(format!("expected a card and a seat but found `{}`", card.trim())),
+        };
+
+        let index = pairs
+            .iter()
+            .position(|&p| p == pair)
+            .ok_or_else(|| {
+                            format!("{} is not part of {}", card_text(pair.0, pair.1), subsuit)
+                        })?;
+
+        claimed[index] = claimed_seat;
+        actual[index] = actual_seat;
+        found[index] = true;
+    }
+
+    if found.iter().any(|&f| !f) {
+        return Err(format!("every card of {} needs to be given to someone", subsuit));
+    }
+
+    let (correct, cancelled) = match outcome {
+        "correct" => (true, false),
+        "wrong" => (false, false),
+        "cancelled" => (false, true),
+        _ => {
+            return Err(format!("expected correct, wrong or cancelled but found `{}`",
+                               outcome))
+        }
+    };
+
+    Ok(Declared(DeclarationRecord {
+                    declarer,
+                    subsuit,
+                    claimed,
+                    actual,
+                    correct,
+                    cancelled,
+                }))
+}
+
+fn parse_seat(text: &str) -> Option<Player> {
+    ALL_SEATS.iter().find(|seat| seat.to_string() == text.trim()).cloned()
+}
+
+fn expect_seat(text: &str) -> Result<Player, String> {
+    parse_seat(text).ok_or_else(|| format!("`{}` is not a seat", text))
+}
+
+//the letters people can type for suits, turned into the symbols we write
+fn normalize_suits(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+                 'C' => '♣',
+                 'D' => '♦',
+                 'H' => '♥',
+                 'S' => '♠',
+                 _ => c,
+             })
+        .collect()
+}
+
+fn parse_card(text: &str) -> Result<(Suit, Value), String> {
+    let normalized = normalize_suits(text);
+
+    Suit::all_values()
+        .into_iter()
+        .flat_map(|suit| Value::all_values().into_iter().map(move |value| (suit, value)))
+        .find(|&(suit, value)| card_text(suit, value) == normalized)
+        .ok_or_else(|| format!("`{}` is not a card", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: &str = "[Game \"Canadian Fish\"]
+[Seed \"7\"]
+[Rules \"standard\"]
+[You \"You\"]
+[TeammateOne \"Tia\"]
+[Result \"*\"]
+
+You: 2♣ 4♣ 2♦ 6♦ 4♥ 6♥ 3♠ 6♠
+TeammateOne: 7♣ Q♣ K♣ 7♦ Q♦ K♦ 2♠ Q♠
+TeammateTwo: 3♣ 9♣ 10♣ A♣ 9♦ A♦ A♥ 7♠
+OpponentZero: 4♦ 10♦ J♦ 3♥ 9♥ 9♠ J♠ A♠
+OpponentOne: 5♣ 6♣ J♣ 3♦ 5♥ Q♥ K♥ 10♠
+OpponentTwo: 5♦ 2♥ 7♥ 10♥ J♥ 4♠ 5♠ K♠
+
+You to play
+You asks OpponentOne for 5♣: yes
+You asks OpponentZero for 3♣: no
+OpponentZero to play
+You declares 2-7 of ♣: 2♣ You, 3♣ TeammateTwo, 4♣ You, 5♣ You, 6♣ You, 7♣ TeammateOne \
+                             (OpponentOne): wrong
+";
+
+    #[test]
+    fn a_written_record_reads_back_the_same() {
+        assert_eq!(write(&parse(RECORD).unwrap()), RECORD);
+    }
+
+    #[test]
+    fn the_moves_are_read_in_order() {
+        let record = parse(RECORD).unwrap();
+
+        assert_eq!(record.seed, Some(7));
+        assert_eq!(record.names.get(&TeammatePlayer(TeammateOne)).map(|s| s.as_str()),
+                   Some("Tia"));
+        assert_eq!(record.moves.len(), 5);
+
+        match record.moves[1] {
+            Asked(ToOpponent(ThePlayer, OpponentOne), Clubs, Five, true) => {}
+            _ => panic!("expected You to get 5♣ from OpponentOne"),
+        }
+
+        match record.moves[4] {
+            Declared(declaration) => {
+                assert_eq!(declaration.subsuit, LowClubs);
+                assert_eq!(declaration.claimed[5], TeammatePlayer(TeammateOne));
+                assert_eq!(declaration.actual[5], OpponentPlayer(OpponentOne));
+                assert!(!declaration.correct);
+            }
+            _ => panic!("expected a declaration"),
+        }
+
+        assert_eq!(record.score(), (0, 1));
+        assert!(!record.finished());
+    }
+
+    #[test]
+    fn suits_can_be_typed_as_letters_and_comments_are_skipped() {
+        let text = RECORD
+            .replace("You asks OpponentOne for 5♣: yes",
+                     "; an easy one to start\nYou asks OpponentOne for 5C: yes");
+        let record = parse(&text).unwrap();
+
+        match record.moves[1] {
+            Asked(_, Clubs, Five, true) => {}
+            _ => panic!("expected the ask for 5♣"),
+        }
+    }
+
+    #[test]
+    fn problems_say_which_line_they_are_on() {
+        let missing_seat = RECORD.replace("OpponentTwo: 5♦ 2♥ 7♥ 10♥ J♥ 4♠ 5♠ K♠\n", "");
+        assert_eq!(parse(&missing_seat).err().unwrap(),
+                   "the cards OpponentTwo was dealt are missing");
+
+        let bad_card = RECORD.replace("for 3♣", "for 8♣");
+        assert_eq!(parse(&bad_card).err().unwrap(), "line 17: `8♣` is not a card");
+
+        let same_team = RECORD.replace("You asks OpponentZero", "You asks TeammateOne");
+        assert_eq!(parse(&same_team).err().unwrap(),
+                   "line 17: You and TeammateOne are on the same team");
+    }
+}
@@ -0,0 +1,467 @@
+//A `Platform` that draws into memory instead of a window, so tests can run
+//the game and look at what it drew.
+//
+//The `Platform` functions are plain `fn`s so the screen lives in a thread
+//local. Each test runs on its own thread and so gets its own screen.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+
+use {Platform, Point, Size, Rect, Color, Event, KeyCode};
+
+type Rgba = (u8, u8, u8, u8);
+
+const WHITE: Rgba = (255, 255, 255, 255);
+const TRANSPARENT: Rgba = (0, 0, 0, 0);
+
+#[derive(Clone, Copy)]
+struct Cell {
+    glyph: char,
+    foreground: Rgba,
+    background: Rgba,
+}
+
+struct Screen {
+    size: Size,
+    //each layer has a cell per position, None where nothing has been drawn
+    layers: HashMap<i32, Vec<Option<Cell>>>,
+    layer: i32,
+    foreground: Rgba,
+    background: Rgba,
+    mouse: Point,
+    keys_down: Vec<KeyCode>,
+    events: Vec<Event>,
+}
+
+impl Screen {
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x >= 0 && y >= 0 && x < self.size.width && y < self.size.height {
+            Some((y * self.size.width + x) as usize)
+        } else {
+            None
+        }
+    }
+
+    //the highest layer with something drawn at this position wins
+    fn top_cell(&self, x: i32, y: i32) -> Option<Cell> {
+        let index = self.index(x, y)?;
+
+        self.layers_top_first()
+            .iter()
+            .filter_map(|layer| self.layers[layer][index])
+            .next()
+    }
+
+    fn layers_top_first(&self) -> Vec<i32> {
+        let mut layers: Vec<i32> = self.layers.keys().cloned().collect();
+        layers.sort_by(|a, b| b.cmp(a));
+
+        layers
+    }
+
+    fn text(&self, layers: &[i32]) -> String {
+        let rows: Vec<String> = (0..self.size.height)
+            .map(|y| {
+                let row: String = (0..self.size.width)
+                    .map(|x| {
+                        let index = (y * self.size.width + x) as usize;
+
+                        layers
+                            .iter()
+                            .filter_map(|layer| self.layers.get(layer).and_then(|cells| cells[index]))
+                            .next()
+                            .map(|cell| cell.glyph)
+                            .unwrap_or(' ')
+                    })
+                    .collect();
+
+                row.trim_end().to_string()
+            })
+            .collect();
+
+        rows.join("\n")
+    }
+}
+
+thread_local! {
+    static SCREEN: RefCell<Screen> = RefCell::new(Screen {
+        size: Size { width: 0, height: 0 },
+        layers: HashMap::new(),
+        layer: 0,
+        foreground: WHITE,
+        background: TRANSPARENT,
+        mouse: Point { x: 0, y: 0 },
+        keys_down: Vec::new(),
+        events: Vec::new(),
+    });
+}
+
+//starts this thread over with a blank screen of the given size
+pub fn platform(width: i32, height: i32) -> Platform {
+    SCREEN.with(|screen| {
+        *screen.borrow_mut() = Screen {
+            size: Size { width, height },
+            layers: HashMap::new(),
+            layer: 0,
+            foreground: WHITE,
+            background: TRANSPARENT,
+            mouse: Point { x: 0, y: 0 },
+            keys_down: Vec::new(),
+            events: Vec::new(),
+        }
+    });
+
+    Platform {
+        print_xy,
+        clear,
+        size,
+        pick,
+        mouse_position,
+        clicks,
+        key_pressed,
+        set_colors,
+        get_colors,
+        set_foreground,
+        get_foreground,
+        set_background,
+        get_background,
+        set_layer,
+        get_layer,
+    }
+}
+
+//queues an event for the next `take_events`
+pub fn push_event(event: Event) {
+    SCREEN.with(|screen| screen.borrow_mut().events.push(event));
+}
+
+pub fn set_mouse_position(point: Point) {
+    SCREEN.with(|screen| screen.borrow_mut().mouse = point);
+}
+
+//hands over the queued events, keeping track of the mouse and held keys the
+//way a real backend would as it reads them
+pub fn take_events() -> Vec<Event> {
+    SCREEN.with(|screen| {
+        let mut screen = screen.borrow_mut();
+        let events: Vec<Event> = screen.events.drain(..).collect();
+
+        for event in events.iter() {
+            match *event {
+                Event::MouseMove { x, y } => screen.mouse = Point { x, y },
+                Event::KeyPressed { key, .. } if !screen.keys_down.contains(&key) => {
+                    screen.keys_down.push(key)
+                }
+                Event::KeyReleased { key, .. } => screen.keys_down.retain(|&k| k != key),
+                _ => {}
+            }
+        }
+
+        events
+    })
+}
+
+//what a player would see, one line per row with trailing spaces removed
+pub fn screen_text() -> String {
+    SCREEN.with(|screen| {
+        let screen = screen.borrow();
+
+        screen.text(&screen.layers_top_first())
+    })
+}
+
+//just what was drawn on one layer, in the same form as `screen_text`
+pub fn layer_text(layer: i32) -> String {
+    SCREEN.with(|screen| screen.borrow().text(&[layer]))
+}
+
+//the colour of the text showing at this position, if there is any
+pub fn foreground_at(x: i32, y: i32) -> Option<Color> {
+    SCREEN.with(|screen| screen.borrow().top_cell(x, y).map(|cell| from_rgba(cell.foreground)))
+}
+
+pub fn background_at(x: i32, y: i32) -> Option<Color> {
+    SCREEN.with(|screen| screen.borrow().top_cell(x, y).map(|cell| from_rgba(cell.background)))
+}
+
+//the position of the first place `text` appears on screen
+pub fn find(text: &str) -> Option<Point> {
+    screen_text()
+        .lines()
+        .enumerate()
+        .filter_map(|(y, line)| {
+                        line.find(text)
+                            .map(|byte_index| {
+                                     Point {
+                                         x: line[..byte_index].chars().count() as i32,
+                                         y: y as i32,
+                                     }
+                                 })
+                    })
+        .next()
+}
+
+//the position of the first place `text` is all there is on a row of a box,
+//the way a button is drawn, so a button can be told from the same words
+//elsewhere
+pub fn find_boxed(text: &str) -> Option<Point> {
+    let rows: Vec<Vec<char>> = screen_text().lines().map(|line| line.chars().collect()).collect();
+    let text: Vec<char> = text.chars().collect();
+
+    for (y, row) in rows.iter().enumerate() {
+        for x in 0..row.len() {
+            if row[x..].starts_with(&text) && is_alone_in_a_box(&rows, x, y, text.len()) {
+                return Some(Point {
+                                x: x as i32,
+                                y: y as i32,
+                            });
+            }
+        }
+    }
+
+    None
+}
+
+//whether there's only blank space between the `width` cells starting at
+//`x`, `y` and the sides of a box around them
+fn is_alone_in_a_box(rows: &[Vec<char>], x: usize, y: usize, width: usize) -> bool {
+    let at = |x: usize, y: usize| rows.get(y).and_then(|row| row.get(x)).cloned().unwrap_or(' ');
+    let is_side = |c: char| c == '│' || c == '║';
+
+    let left = match (0..x).rev().find(|&i| at(i, y) != ' ') {
+        Some(i) => i,
+        None => return false,
+    };
+    let right = match (x + width..rows[y].len()).find(|&i| at(i, y) != ' ') {
+        Some(i) => i,
+        None => return false,
+    };
+    let top = (0..y).rev().map(|j| at(left, j)).find(|&c| !is_side(c));
+    let bottom = (y + 1..rows.len()).map(|j| at(left, j)).find(|&c| !is_side(c));
+
+    is_side(at(left, y)) && is_side(at(right, y)) && top.map(|c| "┌╔".contains(c)).unwrap_or(false) &&
+    bottom.map(|c| "└╘╙╚".contains(c)).unwrap_or(false)
+}
+
+fn print_xy(x: i32, y: i32, text: &str) {
+    SCREEN.with(|screen| {
+        let mut screen = screen.borrow_mut();
+        let cell_count = (screen.size.width * screen.size.height) as usize;
+        let (layer, foreground, background) = (screen.layer, screen.foreground, screen.background);
+
+        for (i, glyph) in text.chars().enumerate() {
+            if let Some(index) = screen.index(x + i as i32, y) {
+                screen.layers.entry(layer).or_insert_with(|| vec![None; cell_count])[index] =
+                    Some(Cell {
+                             glyph,
+                             foreground,
+                             background,
+                         });
+            }
+        }
+    });
+}
+
+//like BearLibTerminal, clearing an area only clears the current layer but
+//clearing everything clears every layer
+fn clear(area: Option<Rect>) {
+    SCREEN.with(|screen| {
+        let mut screen = screen.borrow_mut();
+
+        match area {
+            None => screen.layers.clear(),
+            Some(rect) => {
+                let layer = screen.layer;
+
+                for y in rect.top_left.y..rect.top_left.y + rect.size.height {
+                    for x in rect.top_left.x..rect.top_left.x + rect.size.width {
+                        if let Some(index) = screen.index(x, y) {
+                            if let Some(cells) = screen.layers.get_mut(&layer) {
+                                cells[index] = None;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    });
+}
+
+fn size() -> Size {
+    SCREEN.with(|screen| screen.borrow().size)
+}
+
+fn pick(point: Point, _index: i32) -> char {
+    SCREEN.with(|screen| {
+        let screen = screen.borrow();
+
+        screen
+            .index(point.x, point.y)
+            .and_then(|index| screen.layers.get(&screen.layer).and_then(|cells| cells[index]))
+            .map(|cell| cell.glyph)
+            .unwrap_or(' ')
+    })
+}
+
+fn mouse_position() -> Point {
+    SCREEN.with(|screen| screen.borrow().mouse)
+}
+
+fn clicks() -> i32 {
+    if key_pressed(KeyCode::MouseLeft) { 1 } else { 0 }
+}
+
+fn key_pressed(key: KeyCode) -> bool {
+    SCREEN.with(|screen| screen.borrow().keys_down.contains(&key))
+}
+
+fn to_rgba(color: Color) -> Rgba {
+    (color.red, color.green, color.blue, color.alpha)
+}
+
+fn from_rgba((red, green, blue, alpha): Rgba) -> Color {
+    Color {
+        red,
+        green,
+        blue,
+        alpha,
+    }
+}
+
+fn set_colors(foreground: Color, background: Color) {
+    set_foreground(foreground);
+    set_background(background);
+}
+
+fn get_colors() -> (Color, Color) {
+    (get_foreground(), get_background())
+}
+
+fn set_foreground(color: Color) {
+    SCREEN.with(|screen| screen.borrow_mut().foreground = to_rgba(color));
+}
+
+fn get_foreground() -> Color {
+    from_rgba(SCREEN.with(|screen| screen.borrow().foreground))
+}
+
+fn set_background(color: Color) {
+    SCREEN.with(|screen| screen.borrow_mut().background = to_rgba(color));
+}
+
+fn get_background() -> Color {
+    from_rgba(SCREEN.with(|screen| screen.borrow().background))
+}
+
+fn set_layer(layer: i32) {
+    SCREEN.with(|screen| screen.borrow_mut().layer = layer);
+}
+
+fn get_layer() -> i32 {
+    SCREEN.with(|screen| screen.borrow().layer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn printed_text_shows_up_in_the_screen_text() {
+        let platform = platform(10, 3);
+
+        (platform.print_xy)(2, 1, "hi ♥");
+
+        assert_eq!(screen_text(), "\n  hi ♥\n");
+        assert_eq!(find("♥"), Some(Point { x: 5, y: 1 }));
+    }
+
+    #[test]
+    fn a_label_alone_in_a_box_is_found_before_the_same_words_elsewhere() {
+        let platform = platform(12, 6);
+
+        (platform.print_xy)(0, 0, "┌──────────┐");
+        (platform.print_xy)(0, 1, "│ Bob  8   │");
+        (platform.print_xy)(0, 2, "└──────────┘");
+        (platform.print_xy)(0, 3, "┌───┐");
+        (platform.print_xy)(0, 4, "│Bob│");
+        (platform.print_xy)(0, 5, "╘═══╝");
+
+        assert_eq!(find("Bob"), Some(Point { x: 2, y: 1 }));
+        assert_eq!(find_boxed("Bob"), Some(Point { x: 1, y: 4 }));
+        assert_eq!(find_boxed("8"), None);
+    }
+
+    #[test]
+    fn text_past_the_edge_is_dropped() {
+        let platform = platform(4, 1);
+
+        (platform.print_xy)(2, 0, "abcdef");
+        (platform.print_xy)(-1, 0, "xy");
+
+        assert_eq!(screen_text(), "y ab");
+    }
+
+    #[test]
+    fn clearing_an_area_only_clears_the_current_layer() {
+        let platform = platform(4, 2);
+
+        (platform.print_xy)(0, 0, "aaaa");
+        (platform.set_layer)(1);
+        (platform.print_xy)(0, 0, "bb");
+        (platform.print_xy)(0, 1, "bbbb");
+
+        assert_eq!(screen_text(), "bbaa\nbbbb");
+
+        (platform.clear)(Some(Rect::from_values(0, 0, 1, 2)));
+
+        assert_eq!(screen_text(), "abaa\n bbb");
+        assert_eq!(layer_text(0), "aaaa\n");
+
+        (platform.clear)(None);
+
+        assert_eq!(screen_text(), "\n");
+    }
+
+    #[test]
+    fn cells_keep_the_colours_they_were_drawn_with() {
+        let platform = platform(4, 1);
+
+        (platform.set_foreground)(Color {
+                                      red: 255,
+                                      green: 0,
+                                      blue: 0,
+                                      alpha: 255,
+                                  });
+        (platform.print_xy)(1, 0, "r");
+
+        let foreground = foreground_at(1, 0).unwrap();
+        assert_eq!((foreground.red, foreground.green, foreground.blue), (255, 0, 0));
+        assert!(foreground_at(0, 0).is_none());
+    }
+
+    #[test]
+    fn taking_events_tracks_the_mouse_and_held_keys() {
+        let platform = platform(4, 4);
+
+        push_event(Event::MouseMove { x: 2, y: 3 });
+        push_event(Event::KeyPressed {
+                       key: KeyCode::MouseLeft,
+                       ctrl: false,
+                       shift: false,
+                   });
+
+        assert_eq!(take_events().len(), 2);
+        assert_eq!((platform.mouse_position)(), Point { x: 2, y: 3 });
+        assert!((platform.key_pressed)(KeyCode::MouseLeft));
+
+        push_event(Event::KeyReleased {
+                       key: KeyCode::MouseLeft,
+                       ctrl: false,
+                       shift: false,
+                   });
+        take_events();
+
+        assert!(!(platform.key_pressed)(KeyCode::MouseLeft));
+        assert!(take_events().is_empty());
+    }
+}
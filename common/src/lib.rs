@@ -2,10 +2,17 @@ extern crate rand;
 
 use std::fmt;
 
-use rand::{StdRng, Rand, Rng};
+use rand::{StdRng, Rand, Rng, SeedableRng};
 use std::cmp::Ordering;
 use std::cmp::Ordering::{Less, Equal, Greater};
 use std::collections::HashMap;
+use std::time::Instant;
+use std::path::PathBuf;
+
+pub mod settings_file;
+pub mod save_file;
+pub mod game_record;
+pub mod headless;
 
 pub struct Platform {
     pub print_xy: fn(i32, i32, &str),
@@ -26,7 +33,7 @@ pub struct Platform {
 }
 
 pub struct State {
-    pub rng: StdRng,
+    pub rng: GameRng,
     pub player: Hand,
     pub teammate_1: Hand,
     pub teammate_2: Hand,
@@ -38,6 +45,8 @@ pub struct State {
     pub opponent_1_memory: Memory,
     pub opponent_2_memory: Memory,
     pub opponent_3_memory: Memory,
+    //what the player can deduce, shown in the notebook
+    pub player_memory: Memory,
     pub menu_state: MenuState,
     pub declaration: Option<Declaration>,
     pub ui_context: UIContext,
@@ -46,15 +55,219 @@ pub struct State {
     pub suits_in_play_bits: u8,
     pub player_points: u8,
     pub opponent_points: u8,
+    pub history: Vec<HistoryEntry>,
+    pub side_panel: SidePanel,
+    pub log_scroll: usize,
+    pub notebook_open: bool,
+    pub hand_order: HandOrder,
+    //the card in the player's hand that the window arrows will move
+    pub selected_card: Option<(Suit, Value)>,
+    pub settings: Settings,
+    //what each seat is called this game
+    pub names: HashMap<Player, String>,
+    pub personalities: HashMap<Player, Personality>,
+    //what the seat responding to the current ask or declaration says
+    pub dialogue: Option<String>,
+    pub settings_open: bool,
+    //problems with the settings file, shown on the settings screen
+    pub settings_problems: Vec<String>,
+    //when the current CPU ask result was first shown
+    pub autoplay_started: Option<Instant>,
+    pub autoplay_paused: bool,
+    //a CPU is playing the player's seat
+    pub autopilot: bool,
+    //every seat is a CPU and the player is only watching
+    pub spectating: bool,
+    //show every seat's cards at the table
+    pub reveal_hands: bool,
+    //whose memory the notebook shows
+    pub notebook_seat: Player,
+    //only ever set when built with the `debug` feature
+    pub debug_overlay: Option<DebugOverlay>,
     pub title_screen: bool,
+    //shown on the title screen, for things like a game that couldn't be kept
+    pub notice: Option<String>,
+    //set while stepping through a recorded game rather than playing one
+    pub replay: Option<Replay>,
+    //the records offered on the title screen, as (path, description) pairs
+    pub replay_choices: Option<Vec<(String, String)>>,
+    //which page of `replay_choices` is showing, newest first
+    pub replay_choices_page: usize,
+    //where the settings, the saved game and the game records are kept, None
+    //when there's nowhere to keep them.
+    //Not part of a saved game, it's wherever the game is running now.
+    pub config_dir: Option<PathBuf>,
+}
+
+pub struct Replay {
+    pub record: game_record::GameRecord,
+    //how many asks and declarations have been played out
+    pub step: usize,
+}
+
+//The game's random numbers. StdRng's state can't be saved, so this counts
+//how many numbers have been drawn since seeding, which is enough to get back
+//to exactly the same place. Every `next_u32` and `next_u64` of StdRng uses up
+//one 64 bit number, so a single count covers both.
+pub struct GameRng {
+    rng: StdRng,
+    seed: usize,
+    draws: u64,
+}
+
+impl GameRng {
+    pub fn new(seed: usize) -> GameRng {
+        GameRng::resume(seed, 0)
+    }
+
+    pub fn resume(seed: usize, draws: u64) -> GameRng {
+        let seed_slice: &[_] = &[seed];
+        let mut rng: StdRng = SeedableRng::from_seed(seed_slice);
+
+        for _ in 0..draws {
+            rng.next_u64();
+        }
+
+        GameRng { rng, seed, draws }
+    }
+
+    pub fn seed(&self) -> usize {
+        self.seed
+    }
+
+    pub fn draws(&self) -> u64 {
+        self.draws
+    }
+}
+
+impl Rng for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.draws += 1;
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.draws += 1;
+        self.rng.next_u64()
+    }
+}
+
+//chosen on the title screen and kept when a new game is started
+#[derive(Clone)]
+pub struct Settings {
+    pub difficulty: Difficulty,
+    pub rules: RulesVariant,
+    //None means a random player goes first
+    pub first_player: Option<Player>,
+    //seats without a name here get a random one each game
+    pub names: HashMap<Player, String>,
+    //None means a different deal every game
+    pub seed: Option<usize>,
+    pub autoplay: AutoplaySpeed,
+    //whether asks by the player's team or to the player wait for a click at any speed
+    pub acknowledge_team_asks: bool,
+    pub display: DisplaySettings,
+    //where to load the game code from, None means the default location
+    pub library_path: Option<String>,
+}
+
+//read once when the window is opened
+#[derive(Clone)]
+pub struct DisplaySettings {
+    pub window_width: i32,
+    pub window_height: i32,
+    pub font: String,
+    pub font_width: i32,
+    pub font_height: i32,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> DisplaySettings {
+        DisplaySettings {
+            window_width: 104,
+            window_height: 36,
+            font: "such_graphics.png".to_string(),
+            font_width: 16,
+            font_height: 16,
+        }
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            difficulty: Difficulty::Normal,
+            rules: RulesVariant::Standard,
+            first_player: None,
+            names: vec![(TeammatePlayer(Teammate::ThePlayer), "You".to_string())]
+                .into_iter()
+                .collect(),
+            seed: None,
+            autoplay: AutoplaySpeed::StepByStep,
+            acknowledge_team_asks: true,
+            display: DisplaySettings::default(),
+            library_path: None,
+        }
+    }
+}
+
+//decides which lines a seat says
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Personality {
+    Plain,
+    Cheerful,
+    Grumpy,
+    Smug,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+//how quickly the results of CPU asks go by
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum AutoplaySpeed {
+    Instant,
+    Fast,
+    Normal,
+    //every result waits for a click
+    StepByStep,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RulesVariant {
+    Standard,
+    //a team that holds a whole half-suit but misassigns it scores nobody a point
+    MisassignedCancelled,
 }
 
 pub type UiId = i32;
 
+#[derive(Default)]
 pub struct UIContext {
     pub hot: UiId,
     pub active: UiId,
     pub next_hot: UiId,
+    pub focused: UiId,
+    //the focusable widgets in the order they were drawn this frame
+    pub focus_order: Vec<UiId>,
+    pub keyboard: KeyboardInput,
+    //the text being typed into the focused text field
+    pub editing: Option<(UiId, String)>,
+}
+
+//the keyboard input relevant to the UI for a single frame
+#[derive(Copy, Clone, Default)]
+pub struct KeyboardInput {
+    pub focus_delta: i32,
+    pub activate: bool,
+    pub back: bool,
+    pub typed: Option<char>,
+    pub card_shift: i32,
+    pub number: Option<u8>,
 }
 
 impl UIContext {
@@ -70,11 +283,41 @@ impl UIContext {
     pub fn set_not_hot(&mut self) {
         self.hot = 0;
     }
+    pub fn set_focusable(&mut self, id: UiId) {
+        self.focus_order.push(id);
+    }
+    pub fn is_focused(&self, id: UiId) -> bool {
+        self.focused != 0 && self.focused == id
+    }
     pub fn frame_init(&mut self) {
         if self.active == 0 {
             self.hot = self.next_hot;
         }
         self.next_hot = 0;
+
+        //focus moves through the widgets drawn last frame, wrapping around
+        let len = self.focus_order.len() as i32;
+        if self.keyboard.focus_delta != 0 && len > 0 {
+            let index = match self.focus_order.iter().position(|&id| id == self.focused) {
+                Some(i) => i as i32 + self.keyboard.focus_delta,
+                None if self.keyboard.focus_delta > 0 => 0,
+                None => -1,
+            };
+
+            self.focused = self.focus_order[(((index % len) + len) % len) as usize];
+        }
+
+        //half-typed text is dropped once its field loses focus or goes away,
+        //so the field shows the real value again next time
+        let still_editing = match self.editing {
+            Some((id, _)) => id == self.focused && self.focus_order.contains(&id),
+            None => false,
+        };
+        if !still_editing {
+            self.editing = None;
+        }
+
+        self.focus_order.clear();
     }
 }
 
@@ -93,6 +336,15 @@ pub enum AskVector {
     ToTeammate(Opponent, Teammate),
     ToOpponent(Teammate, Opponent),
 }
+use AskVector::*;
+
+//who is asking and who is being asked
+pub fn ask_vector_players(ask_vector: AskVector) -> (Player, Player) {
+    match ask_vector {
+        ToTeammate(source, target) => (OpponentPlayer(source), TeammatePlayer(target)),
+        ToOpponent(source, target) => (TeammatePlayer(source), OpponentPlayer(target)),
+    }
+}
 
 #[derive(Copy, Clone)]
 pub enum Declaration {
@@ -107,7 +359,55 @@ pub enum DeclarationInfo {
     OpponentDInfo(Opponent, SubSuit, [Opponent; 6]),
 }
 
+//everything that has happened so far in a game is kept as a list of these
 #[derive(Copy, Clone)]
+pub enum HistoryEntry {
+    Asked(AskVector, Suit, Value, bool),
+    TurnPassed(Player),
+    Declared(DeclarationRecord),
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum DebugOverlay {
+    //exactly what this seat's Memory holds
+    RawMemory(Player),
+    TrueHands,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum HandOrder {
+    AutoSorted,
+    GroupedByHalfSuit,
+    Manual,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum SidePanel {
+    DeclarationHistory,
+    GameLog,
+}
+
+//what happened when a half-suit was declared
+#[derive(Copy, Clone)]
+pub struct DeclarationRecord {
+    pub declarer: Player,
+    pub subsuit: SubSuit,
+    pub claimed: [Player; 6],
+    pub actual: [Player; 6],
+    pub correct: bool,
+    pub cancelled: bool,
+}
+
+impl DeclarationRecord {
+    pub fn scored_for_player_team(&self) -> bool {
+        match self.declarer {
+            TeammatePlayer(_) => self.correct,
+            OpponentPlayer(_) => !self.correct,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum SubSuit {
     LowClubs,
     HighClubs,
@@ -137,6 +437,87 @@ impl fmt::Display for SubSuit {
     }
 }
 
+pub fn subsuit_from_pair(suit: Suit, value: Value) -> SubSuit {
+    let is_low = u8::from(value) < 8;
+
+    match suit {
+        Clubs => if is_low { LowClubs } else { HighClubs },
+        Diamonds => if is_low { LowDiamonds } else { HighDiamonds },
+        Hearts => if is_low { LowHearts } else { HighHearts },
+        Spades => if is_low { LowSpades } else { HighSpades },
+    }
+}
+
+//in the order declarations list them
+pub fn pairs_from_subsuit(subsuit: SubSuit) -> Vec<(Suit, Value)> {
+    match subsuit {
+        LowClubs => {
+            vec![(Clubs, Two),
+                 (Clubs, Three),
+                 (Clubs, Four),
+                 (Clubs, Five),
+                 (Clubs, Six),
+                 (Clubs, Seven)]
+        }
+        HighClubs => {
+            vec![(Clubs, Nine),
+                 (Clubs, Ten),
+                 (Clubs, Jack),
+                 (Clubs, Queen),
+                 (Clubs, King),
+                 (Clubs, Ace)]
+        }
+        LowDiamonds => {
+            vec![(Diamonds, Two),
+                 (Diamonds, Three),
+                 (Diamonds, Four),
+                 (Diamonds, Five),
+                 (Diamonds, Six),
+                 (Diamonds, Seven)]
+        }
+        HighDiamonds => {
+            vec![(Diamonds, Nine),
+                 (Diamonds, Ten),
+                 (Diamonds, Jack),
+                 (Diamonds, Queen),
+                 (Diamonds, King),
+                 (Diamonds, Ace)]
+        }
+        LowHearts => {
+            vec![(Hearts, Two),
+                 (Hearts, Three),
+                 (Hearts, Four),
+                 (Hearts, Five),
+                 (Hearts, Six),
+                 (Hearts, Seven)]
+        }
+        HighHearts => {
+            vec![(Hearts, Nine),
+                 (Hearts, Ten),
+                 (Hearts, Jack),
+                 (Hearts, Queen),
+                 (Hearts, King),
+                 (Hearts, Ace)]
+        }
+        LowSpades => {
+            vec![(Spades, Two),
+                 (Spades, Three),
+                 (Spades, Four),
+                 (Spades, Five),
+                 (Spades, Six),
+                 (Spades, Seven)]
+        }
+        HighSpades => {
+            vec![(Spades, Nine),
+                 (Spades, Ten),
+                 (Spades, Jack),
+                 (Spades, Queen),
+                 (Spades, King),
+                 (Spades, Ace)]
+        }
+    }
+}
+
 //bitmasks for use with suits_in_play_bits
 impl From<SubSuit> for u8 {
     fn from(subsuit: SubSuit) -> Self {
@@ -207,6 +588,14 @@ impl Rand for Player {
     }
 }
 
+//every seat, in the order the table, the notebook and game records list them
+pub const ALL_SEATS: [Player; 6] = [TeammatePlayer(ThePlayer),
+                                    TeammatePlayer(TeammateOne),
+                                    TeammatePlayer(TeammateTwo),
+                                    OpponentPlayer(OpponentZero),
+                                    OpponentPlayer(OpponentOne),
+                                    OpponentPlayer(OpponentTwo)];
+
 pub fn cpu_players() -> Vec<Player> {
     vec![TeammatePlayer(TeammateOne),
          TeammatePlayer(TeammateTwo),
@@ -463,6 +852,8 @@ use ModelCard::*;
 #[derive(Copy, Clone, Debug,)]
 pub enum Fact {
     KnownNotToHave(Suit, Value),
+    //players can only ask for cards in half-suits they hold
+    KnownToHaveSomeOf(SubSuit),
 }
 
 pub struct Knowledge {
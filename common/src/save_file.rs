@@ -0,0 +1,693 @@
+//A whole game as text, so it can outlive the code that's running it.
+//
+//The first line says which version of the format follows, then each field
+//of the State gets a line of its own, starting with the field's name.
+//Values are space separated words, with text in double quotes.
+//A field that isn't in the text keeps whatever the template passed to
+//`read` has, which is how saves from before a field was added still load.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::Hash;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use *;
+
+const HEADER: &str = "canadian-fish state";
+
+//the game left when the player last quit, next to the settings
+const FILE_NAME: &str = "saved_game.txt";
+
+//Bump this when the meaning or format of an existing field changes, and
+//either convert the older text in `migrate` or raise OLDEST_READABLE_VERSION.
+//Adding a field doesn't need a bump.
+pub const VERSION: u32 = 1;
+const OLDEST_READABLE_VERSION: u32 = 1;
+
+pub fn write(state: &State) -> String {
+    let mut result = format!("{} {}\n", HEADER, VERSION);
+
+    write_fields(state, &mut result);
+
+    result
+}
+
+//fields missing from `text` are left as they are in `template`
+pub fn read(text: &str, template: State) -> Result<State, String> {
+    let mut lines = text.lines();
+
+    let version = read_version(lines.next())?;
+
+    let lines = migrate(version, lines.map(|line| line.to_string()).collect());
+
+    let mut state = template;
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let mut words = Words::new(line);
+
+        read_field(&mut state, &mut words)
+            .and_then(|_| words.finish())
+            .map_err(|problem| format!("line {}: {}", i + 2, problem))?;
+    }
+
+    Ok(state)
+}
+
+//the format version from the header line, if this version can read it
+fn read_version(header: Option<&str>) -> Result<u32, String> {
+    let version = header
+        .and_then(|line| line.strip_prefix(HEADER))
+        .and_then(|version| version.trim().parse::<u32>().ok())
+        .ok_or_else(|| "this is not a saved game".to_string())?;
+
+    if version > VERSION {
+        return Err(format!("it was saved by a newer version of the game (format {}, this \
+                            version reads up to {})",
+                           version,
+                           VERSION));
+    }
+    if version < OLDEST_READABLE_VERSION {
+        return Err(format!("it was saved in format {}, which this version can no longer read",
+                           version));
+    }
+
+    Ok(version)
+}
+
+pub fn saved_game_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(FILE_NAME)
+}
+
+pub fn has_saved_game(config_dir: Option<&Path>) -> bool {
+    config_dir.map(|dir| saved_game_path(dir).is_file()).unwrap_or(false)
+}
+
+pub fn save_game(config_dir: Option<&Path>, state: &State) -> Result<(), String> {
+    let dir = config_dir
+        .ok_or_else(|| "Could not find a config directory to save the game to".to_string())?;
+
+    fs::create_dir_all(dir)
+        .map_err(|error| format!("Could not create {}: {}", dir.display(), error))?;
+
+    let path = saved_game_path(dir);
+
+    File::create(&path)
+        .and_then(|mut file| file.write_all(write(state).as_bytes()))
+        .map_err(|error| format!("Could not write {}: {}", path.display(), error))
+}
+
+pub fn load_game(config_dir: Option<&Path>, template: State) -> Result<State, String> {
+    let path = config_dir
+        .map(saved_game_path)
+        .ok_or_else(|| "Could not find a config directory to load the game from".to_string())?;
+
+    let mut text = String::new();
+    File::open(&path)
+        .and_then(|mut file| file.read_to_string(&mut text))
+        .map_err(|error| format!("Could not read {}: {}", path.display(), error))?;
+
+    read(&text, template).map_err(|problem| format!("Could not load the game, {}", problem))
+}
+
+//once a game is over there's nothing to continue
+pub fn delete_saved_game(config_dir: Option<&Path>) -> Result<(), String> {
+    let path = match config_dir {
+        Some(dir) => saved_game_path(dir),
+        None => return Ok(()),
+    };
+
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(ref error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(format!("Could not remove {}: {}", path.display(), error)),
+    }
+}
+
+//turns the lines of an older version into the current version's lines
+fn migrate(_version: u32, lines: Vec<String>) -> Vec<String> {
+    lines
+}
+
+//the order here is the order they are written in
+macro_rules! fields {
+    ($($field:ident),* $(,)*) => {
+        fn write_fields(state: &State, result: &mut String) {
+            $(
+                let mut words = Vec::new();
+                state.$field.write(&mut words);
+
+                result.push_str(stringify!($field));
+                for word in words {
+                    result.push(' ');
+                    result.push_str(&word);
+                }
+                result.push('\n');
+            )*
+        }
+
+        fn read_field(state: &mut State, words: &mut Words) -> Result<(), String> {
+            let name = words.next_word()?;
+
+            match name.as_str() {
+                $(stringify!($field) => state.$field = Save::read(words)?,)*
+                _ => return Err(format!("`{}` is not part of a game", name)),
+            }
+
+            Ok(())
+        }
+    }
+}
+
+//`ui_context` and `autoplay_started` are only about the current moment on
+//screen so they aren't kept
+fields!(title_screen,
+        rng,
+        player,
+        teammate_1,
+        teammate_2,
+        opponent_1,
+        opponent_2,
+        opponent_3,
+        player_memory,
+        teammate_1_memory,
+        teammate_2_memory,
+        opponent_1_memory,
+        opponent_2_memory,
+        opponent_3_memory,
+        menu_state,
+        declaration,
+        current_player,
+        card_offset,
+        suits_in_play_bits,
+        player_points,
+        opponent_points,
+        history,
+        side_panel,
+        log_scroll,
+        notebook_open,
+        hand_order,
+        selected_card,
+        settings,
+        names,
+        personalities,
+        dialogue,
+        settings_open,
+        settings_problems,
+        autoplay_paused,
+        autopilot,
+        spectating,
+        reveal_hands,
+        notebook_seat,
+        debug_overlay,
+        notice,
+        replay,
+        replay_choices,
+        replay_choices_page);
+
+//the words of a single line
+pub struct Words {
+    words: Vec<String>,
+    index: usize,
+}
+
+impl Words {
+    fn new(line: &str) -> Words {
+        let mut words = Vec::new();
+        let mut chars = line.chars();
+
+        while let Some(c) = chars.next() {
+            if c.is_whitespace() {
+                continue;
+            }
+
+            let mut word = c.to_string();
+
+            if c == '"' {
+                //keep the quotes and escapes so `read` for String can tell
+                //an empty string from a missing one
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    word.push(c);
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == '"' {
+                        break;
+                    }
+                }
+            } else {
+                for c in chars.by_ref() {
+                    if c.is_whitespace() {
+                        break;
+                    }
+                    word.push(c);
+                }
+            }
+
+            words.push(word);
+        }
+
+        Words { words, index: 0 }
+    }
+
+    fn next_word(&mut self) -> Result<String, String> {
+        let word = self.words
+            .get(self.index)
+            .cloned()
+            .ok_or_else(|| "the line ended early".to_string())?;
+        self.index += 1;
+
+        Ok(word)
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        match self.words.get(self.index) {
+            Some(word) => Err(format!("unexpected `{}` at the end of the line", word)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn unexpected(word: &str, expected: &str) -> String {
+    format!("expected {} but found `{}`", expected, word)
+}
+
+pub trait Save: Sized {
+    fn write(&self, words: &mut Vec<String>);
+    fn read(words: &mut Words) -> Result<Self, String>;
+}
+
+macro_rules! save_number {
+    ($($type:ty),*) => {
+        $(
+            impl Save for $type {
+                fn write(&self, words: &mut Vec<String>) {
+                    words.push(self.to_string());
+                }
+
+                fn read(words: &mut Words) -> Result<Self, String> {
+                    let word = words.next_word()?;
+
+                    word.parse().map_err(|_| unexpected(&word, "a number"))
+                }
+            }
+        )*
+    }
+}
+
+save_number!(u8, u64, usize, i32);
+
+impl Save for bool {
+    fn write(&self, words: &mut Vec<String>) {
+        words.push(if *self { "yes" } else { "no" }.to_string());
+    }
+
+    fn read(words: &mut Words) -> Result<Self, String> {
+        let word = words.next_word()?;
+
+        match word.as_str() {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            _ => Err(unexpected(&word, "yes or no")),
+        }
+    }
+}
+
+impl Save for String {
+    fn write(&self, words: &mut Vec<String>) {
+        let mut word = "\"".to_string();
+
+        for c in self.chars() {
+            match c {
+                '"' => word.push_str("\\\""),
+                '\\' => word.push_str("\\\\"),
+                '\n' => word.push_str("\\n"),
+                _ => word.push(c),
+            }
+        }
+
+        word.push('"');
+        words.push(word);
+    }
+
+    fn read(words: &mut Words) -> Result<Self, String> {
+        let word = words.next_word()?;
+
+        if word.len() < 2 || !word.starts_with('"') || !word.ends_with('"') {
+            return Err(unexpected(&word, "text in double quotes"));
+        }
+
+        let mut result = String::new();
+        let mut chars = word[1..word.len() - 1].chars();
+
+        while let Some(c) = chars.next() {
+            if c == '\\' {
+                match chars.next() {
+                    Some('n') => result.push('\n'),
+                    Some(c) => result.push(c),
+                    None => return Err(unexpected(&word, "text in double quotes")),
+                }
+            } else {
+                result.push(c);
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+impl<T: Save> Save for Option<T> {
+    fn write(&self, words: &mut Vec<String>) {
+        match *self {
+            Some(ref value) => {
+                words.push("some".to_string());
+                value.write(words);
+            }
+            None => words.push("none".to_string()),
+        }
+    }
+
+    fn read(words: &mut Words) -> Result<Self, String> {
+        let word = words.next_word()?;
+
+        match word.as_str() {
+            "some" => Ok(Some(T::read(words)?)),
+            "none" => Ok(None),
+            _ => Err(unexpected(&word, "some or none")),
+        }
+    }
+}
+
+//the length first, then each element
+impl<T: Save> Save for Vec<T> {
+    fn write(&self, words: &mut Vec<String>) {
+        self.len().write(words);
+
+        for element in self.iter() {
+            element.write(words);
+        }
+    }
+
+    fn read(words: &mut Words) -> Result<Self, String> {
+        let len = usize::read(words)?;
+
+        (0..len).map(|_| T::read(words)).collect()
+    }
+}
+
+impl<T: Save + Copy> Save for [T; 6] {
+    fn write(&self, words: &mut Vec<String>) {
+        for element in self.iter() {
+            element.write(words);
+        }
+    }
+
+    fn read(words: &mut Words) -> Result<Self, String> {
+        let first = T::read(words)?;
+        let mut result = [first; 6];
+
+        for element in result.iter_mut().skip(1) {
+            *element = T::read(words)?;
+        }
+
+        Ok(result)
+    }
+}
+
+impl<A: Save, B: Save> Save for (A, B) {
+    fn write(&self, words: &mut Vec<String>) {
+        self.0.write(words);
+        self.1.write(words);
+    }
+
+    fn read(words: &mut Words) -> Result<Self, String> {
+        let a = A::read(words)?;
+        let b = B::read(words)?;
+
+        Ok((a, b))
+    }
+}
+
+//entries are sorted so the same map is always written the same way
+impl<K: Save + Eq + Hash, V: Save> Save for HashMap<K, V> {
+    fn write(&self, words: &mut Vec<String>) {
+        let mut entries: Vec<Vec<String>> = self.iter()
+            .map(|(key, value)| {
+                     let mut entry = Vec::new();
+                     key.write(&mut entry);
+                     value.write(&mut entry);
+                     entry
+                 })
+            .collect();
+        entries.sort();
+
+        self.len().write(words);
+        for entry in entries {
+            words.extend(entry);
+        }
+    }
+
+    fn read(words: &mut Words) -> Result<Self, String> {
+        let len = usize::read(words)?;
+
+        (0..len).map(|_| <(K, V)>::read(words)).collect()
+    }
+}
+
+//enums are written as the variant's name followed by its fields
+macro_rules! save_enum {
+    ($type:ident { $($variant:ident $(($($field:ident: $field_type:ty),*))*),* $(,)* }) => {
+        impl Save for $type {
+            fn write(&self, words: &mut Vec<String>) {
+                match *self {
+                    $(
+                        $type::$variant $(($(ref $field),*))* => {
+                            words.push(stringify!($variant).to_string());
+                            $($($field.write(words);)*)*
+                        }
+                    )*
+                }
+            }
+
+            fn read(words: &mut Words) -> Result<Self, String> {
+                let word = words.next_word()?;
+
+                match word.as_str() {
+                    $(
+                        stringify!($variant) => {
+                            Ok($type::$variant $(($(<$field_type>::read(words)?),*))*)
+                        }
+                    )*
+                    _ => Err(unexpected(&word, stringify!($type))),
+                }
+            }
+        }
+    }
+}
+
+save_enum!(Suit { Clubs, Diamonds, Hearts, Spades });
+save_enum!(Value { Ace, Two, Three, Four, Five, Six, Seven, Nine, Ten, Jack, Queen, King });
+save_enum!(SubSuit {
+               LowClubs,
+               HighClubs,
+               LowDiamonds,
+               HighDiamonds,
+               LowHearts,
+               HighHearts,
+               LowSpades,
+               HighSpades,
+           });
+save_enum!(Opponent { OpponentZero, OpponentOne, OpponentTwo });
+save_enum!(Teammate { ThePlayer, TeammateOne, TeammateTwo });
+save_enum!(Player { OpponentPlayer(opponent: Opponent), TeammatePlayer(teammate: Teammate) });
+save_enum!(ModelCard { Unknown, Known(suit: Suit, value: Value) });
+save_enum!(Fact {
+               KnownNotToHave(suit: Suit, value: Value),
+               KnownToHaveSomeOf(subsuit: SubSuit),
+           });
+save_enum!(AskVector {
+               ToTeammate(opponent: Opponent, teammate: Teammate),
+               ToOpponent(teammate: Teammate, opponent: Opponent),
+           });
+save_enum!(MenuState {
+               Main,
+               AskStep1,
+               AskStep2(opponent: Opponent),
+               AskStep3(opponent: Opponent, subsuit: SubSuit),
+               AskStep4(ask_vector: AskVector, suit: Suit, value: Value),
+               Quit,
+           });
+save_enum!(DeclarationInfo {
+               TeammateDInfo(teammate: Teammate, subsuit: SubSuit, teammates: [Teammate; 6]),
+               OpponentDInfo(opponent: Opponent, subsuit: SubSuit, opponents: [Opponent; 6]),
+           });
+save_enum!(Declaration {
+               DeclareStep1,
+               DeclareStep2(subsuit: SubSuit, teammates: [Teammate; 6]),
+               DeclareStep3(info: DeclarationInfo),
+           });
+save_enum!(HistoryEntry {
+               Asked(ask_vector: AskVector, suit: Suit, value: Value, has_card: bool),
+               TurnPassed(player: Player),
+               Declared(record: DeclarationRecord),
+           });
+save_enum!(SidePanel { DeclarationHistory, GameLog });
+save_enum!(HandOrder { AutoSorted, GroupedByHalfSuit, Manual });
+save_enum!(Personality { Plain, Cheerful, Grumpy, Smug });
+save_enum!(Difficulty { Easy, Normal, Hard });
+save_enum!(AutoplaySpeed { Instant, Fast, Normal, StepByStep });
+save_enum!(RulesVariant { Standard, MisassignedCancelled });
+save_enum!(DebugOverlay { RawMemory(player: Player), TrueHands });
+
+//structs are written as their fields in order
+macro_rules! save_struct {
+    ($type:ident { $($field:ident: $field_type:ty),* $(,)* }) => {
+        impl Save for $type {
+            fn write(&self, words: &mut Vec<String>) {
+                $(self.$field.write(words);)*
+            }
+
+            fn read(words: &mut Words) -> Result<Self, String> {
+                Ok($type { $($field: <$field_type>::read(words)?),* })
+            }
+        }
+    }
+}
+
+save_struct!(Card { suit: Suit, value: Value });
+save_struct!(Knowledge { model_hand: Vec<ModelCard>, facts: Vec<Fact> });
+save_struct!(DeclarationRecord {
+                 declarer: Player,
+                 subsuit: SubSuit,
+                 claimed: [Player; 6],
+                 actual: [Player; 6],
+                 correct: bool,
+                 cancelled: bool,
+             });
+save_struct!(DisplaySettings {
+                 window_width: i32,
+                 window_height: i32,
+                 font: String,
+                 font_width: i32,
+                 font_height: i32,
+             });
+save_struct!(Settings {
+                 difficulty: Difficulty,
+                 rules: RulesVariant,
+                 first_player: Option<Player>,
+                 names: HashMap<Player, String>,
+                 seed: Option<usize>,
+                 autoplay: AutoplaySpeed,
+                 acknowledge_team_asks: bool,
+                 display: DisplaySettings,
+                 library_path: Option<String>,
+             });
+
+save_struct!(Replay { record: game_record::GameRecord, step: usize });
+
+//a record is kept in the same notation it's shared in
+impl Save for game_record::GameRecord {
+    fn write(&self, words: &mut Vec<String>) {
+        game_record::write(self).write(words);
+    }
+
+    fn read(words: &mut Words) -> Result<Self, String> {
+        game_record::parse(&String::read(words)?)
+    }
+}
+
+//the seed and how many numbers have been drawn since
+impl Save for GameRng {
+    fn write(&self, words: &mut Vec<String>) {
+        self.seed().write(words);
+        self.draws().write(words);
+    }
+
+    fn read(words: &mut Words) -> Result<Self, String> {
+        let seed = usize::read(words)?;
+        let draws = u64::read(words)?;
+
+        Ok(GameRng::resume(seed, draws))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Save>(value: &T) -> T {
+        let mut words = Vec::new();
+        value.write(&mut words);
+
+        let mut words = Words::new(&words.join(" "));
+        let result = T::read(&mut words).unwrap();
+        words.finish().unwrap();
+
+        result
+    }
+
+    #[test]
+    fn text_keeps_its_quotes_spaces_and_newlines() {
+        for text in &["", "Ann", "two words", "a \"quote\"", "back\\slash", "line\nbreak"] {
+            assert_eq!(&round_trip(&text.to_string()), text);
+        }
+    }
+
+    #[test]
+    fn enums_keep_their_fields() {
+        let card = Known(Suit::Hearts, Value::Jack);
+        let player = OpponentPlayer(Opponent::OpponentTwo);
+
+        assert_eq!(round_trip(&card), card);
+        assert_eq!(round_trip(&player), player);
+        assert_eq!(round_trip(&Some(Suit::Spades)), Some(Suit::Spades));
+        assert_eq!(round_trip(&Vec::<Suit>::new()), Vec::new());
+    }
+
+    #[test]
+    fn maps_are_always_written_in_the_same_order() {
+        let mut words = Vec::new();
+        let map: HashMap<Suit, u8> = vec![(Suit::Spades, 1), (Suit::Clubs, 2), (Suit::Hearts, 3)]
+            .into_iter()
+            .collect();
+        map.write(&mut words);
+
+        assert_eq!(words.join(" "), "3 Clubs 2 Hearts 3 Spades 1");
+        assert_eq!(round_trip(&map), map);
+    }
+
+    #[test]
+    fn the_random_numbers_carry_on_where_they_left_off() {
+        let mut rng = GameRng::new(7);
+        rng.next_u32();
+        rng.next_u64();
+
+        let mut resumed = round_trip(&rng);
+
+        assert_eq!(resumed.next_u32(), rng.next_u32());
+    }
+
+    #[test]
+    fn a_save_from_a_newer_version_is_refused() {
+        let header = format!("{} {}", HEADER, VERSION + 1);
+
+        let problem = read_version(Some(&header)).err().unwrap();
+
+        assert!(problem.contains("newer version"), "{}", problem);
+        assert_eq!(read_version(Some(&format!("{} {}", HEADER, VERSION))), Ok(VERSION));
+    }
+
+    #[test]
+    fn a_malformed_value_says_what_was_expected() {
+        let mut words = Words::new("maybe");
+
+        assert_eq!(bool::read(&mut words),
+                   Err("expected yes or no but found `maybe`".to_string()));
+    }
+}
@@ -0,0 +1,357 @@
+//Settings are kept in a plain text file of `key = value` lines in the
+//user's config directory. Lines starting with `#` are comments.
+//Anything we can't make sense of is reported and otherwise ignored, so a
+//bad file never stops the game from starting.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use {Settings, Difficulty, RulesVariant, AutoplaySpeed, Player, AllValues};
+
+const DIRECTORY_NAME: &str = "canadian-fish";
+const FILE_NAME: &str = "settings.txt";
+
+pub fn settings_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(FILE_NAME)
+}
+
+//our folder in the user's config directory, where the settings, the saved
+//game and the game records are kept
+pub fn config_dir() -> Option<PathBuf> {
+    let config_dir = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")));
+
+    config_dir.map(|dir| dir.join(DIRECTORY_NAME))
+}
+
+//returns the settings along with a description of each problem found.
+//A missing file is not a problem, it just means the defaults are used.
+pub fn load(config_dir: Option<&Path>) -> (Settings, Vec<String>) {
+    let path = match config_dir {
+        Some(dir) => settings_path(dir),
+        None => {
+            return (Settings::default(),
+                    vec!["Could not find a config directory to load settings from".to_string()])
+        }
+    };
+
+    let mut text = String::new();
+    match File::open(&path).and_then(|mut file| file.read_to_string(&mut text)) {
+        Ok(_) => parse(&text),
+        Err(ref error) if error.kind() == ::std::io::ErrorKind::NotFound => {
+            (Settings::default(), Vec::new())
+        }
+        Err(error) => {
+            (Settings::default(), vec![format!("Could not read {}: {}", path.display(), error)])
+        }
+    }
+}
+
+pub fn save(config_dir: Option<&Path>, settings: &Settings) -> Result<(), String> {
+    let dir = config_dir
+        .ok_or_else(|| "Could not find a config directory to save settings to".to_string())?;
+
+    fs::create_dir_all(dir)
+        .map_err(|error| format!("Could not create {}: {}", dir.display(), error))?;
+
+    let path = settings_path(dir);
+
+    File::create(&path)
+        .and_then(|mut file| file.write_all(to_string(settings).as_bytes()))
+        .map_err(|error| format!("Could not write {}: {}", path.display(), error))
+}
+
+pub fn parse(text: &str) -> (Settings, Vec<String>) {
+    let mut settings = Settings::default();
+    let mut problems = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line_number = i + 1;
+
+        let (key, value) = match line.find('=') {
+            Some(index) => (line[..index].trim(), line[index + 1..].trim()),
+            None => {
+                problems.push(format!("line {}: expected `key = value` but found `{}`",
+                                      line_number,
+                                      line));
+                continue;
+            }
+        };
+
+        if let Err(problem) = set(&mut settings, key, value) {
+            problems.push(format!("line {}: {}", line_number, problem));
+        }
+    }
+
+    (settings, problems)
+}
+
+fn set(settings: &mut Settings, key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "window_size" => {
+            let (width, height) = parse_dimensions(value)?;
+            settings.display.window_width = width;
+            settings.display.window_height = height;
+        }
+        "font" => settings.display.font = value.to_string(),
+        "font_size" => {
+            let (width, height) = parse_dimensions(value)?;
+            settings.display.font_width = width;
+            settings.display.font_height = height;
+        }
+        "library_path" => {
+            settings.library_path = if value.is_empty() {
+                None
+            } else {
+                Some(value.to_string())
+            };
+        }
+        "difficulty" => {
+            settings.difficulty = match value {
+                "easy" => Difficulty::Easy,
+                "normal" => Difficulty::Normal,
+                "hard" => Difficulty::Hard,
+                _ => return Err(bad_value(key, value, "easy, normal or hard")),
+            };
+        }
+        "rules" => {
+            settings.rules = match value {
+                "standard" => RulesVariant::Standard,
+                "misassigned_cancelled" => RulesVariant::MisassignedCancelled,
+                _ => return Err(bad_value(key, value, "standard or misassigned_cancelled")),
+            };
+        }
+        "first_player" => {
+            settings.first_player = if value == "random" {
+                None
+            } else {
+                Some(parse_player(value).ok_or_else(|| {
+                                                    bad_value(key, value, "random or a seat")
+                                                })?)
+            };
+        }
+        "seed" => {
+            settings.seed = if value == "random" {
+                None
+            } else {
+                Some(value
+                         .parse()
+                         .map_err(|_| bad_value(key, value, "random or a whole number"))?)
+            };
+        }
+        "autoplay" => {
+            settings.autoplay = match value {
+                "instant" => AutoplaySpeed::Instant,
+                "fast" => AutoplaySpeed::Fast,
+                "normal" => AutoplaySpeed::Normal,
+                "step_by_step" => AutoplaySpeed::StepByStep,
+                _ => return Err(bad_value(key, value, "instant, fast, normal or step_by_step")),
+            };
+        }
+        "acknowledge_team_asks" => {
+            settings.acknowledge_team_asks = match value {
+                "yes" => true,
+                "no" => false,
+                _ => return Err(bad_value(key, value, "yes or no")),
+            };
+        }
+        _ if key.starts_with("name.") => {
+            let seat = &key["name.".len()..];
+            let player = parse_player(seat)
+                .ok_or_else(|| format!("`{}` is not a seat", seat))?;
+
+            settings.names.insert(player, value.to_string());
+        }
+        _ => return Err(format!("unknown setting `{}`", key)),
+    }
+
+    Ok(())
+}
+
+fn bad_value(key: &str, value: &str, expected: &str) -> String {
+    format!("`{}` is not a valid {}, expected {}", value, key, expected)
+}
+
+pub fn parse_dimensions(value: &str) -> Result<(i32, i32), String> {
+    let mut parts = value.split('x').map(|part| part.trim().parse::<i32>());
+
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(Ok(width)), Some(Ok(height)), None) if width > 0 && height > 0 => {
+            Ok((width, height))
+        }
+        _ => Err(format!("`{}` is not a size like 104x36", value)),
+    }
+}
+
+fn parse_player(value: &str) -> Option<Player> {
+    Player::all_values().into_iter().find(|p| p.to_string() == value)
+}
+
+pub fn to_string(settings: &Settings) -> String {
+    let mut result = String::new();
+
+    result.push_str("# display, these take effect the next time the game starts\n");
+    result.push_str(&format!("window_size = {}x{}\n",
+                             settings.display.window_width,
+                             settings.display.window_height));
+    result.push_str(&format!("font = {}\n", settings.display.font));
+    result.push_str(&format!("font_size = {}x{}\n",
+                             settings.display.font_width,
+                             settings.display.font_height));
+    if let Some(ref library_path) = settings.library_path {
+        result.push_str(&format!("library_path = {}\n", library_path));
+    }
+
+    result.push_str("\n# rules\n");
+    result.push_str(&format!("rules = {}\n",
+                             match settings.rules {
+                                 RulesVariant::Standard => "standard",
+                                 RulesVariant::MisassignedCancelled => "misassigned_cancelled",
+                             }));
+    result.push_str(&format!("first_player = {}\n",
+                             settings
+                                 .first_player
+                                 .map(|p| p.to_string())
+                                 .unwrap_or_else(|| "random".to_string())));
+    result.push_str(&format!("seed = {}\n",
+                             settings
+                                 .seed
+                                 .map(|seed| seed.to_string())
+                                 .unwrap_or_else(|| "random".to_string())));
+
+    result.push_str("\n# cpu players\n");
+    result.push_str(&format!("difficulty = {}\n",
+                             match settings.difficulty {
+                                 Difficulty::Easy => "easy",
+                                 Difficulty::Normal => "normal",
+                                 Difficulty::Hard => "hard",
+                             }));
+    result.push_str(&format!("autoplay = {}\n",
+                             match settings.autoplay {
+                                 AutoplaySpeed::Instant => "instant",
+                                 AutoplaySpeed::Fast => "fast",
+                                 AutoplaySpeed::Normal => "normal",
+                                 AutoplaySpeed::StepByStep => "step_by_step",
+                             }));
+    result.push_str(&format!("acknowledge_team_asks = {}\n",
+                             if settings.acknowledge_team_asks {
+                                 "yes"
+                             } else {
+                                 "no"
+                             }));
+
+    result.push_str("\n# names\n");
+    for player in Player::all_values() {
+        if let Some(name) = settings.names.get(&player) {
+            result.push_str(&format!("name.{} = {}\n", player, name));
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Player::*;
+    use Opponent::*;
+    use Teammate::*;
+
+    #[test]
+    fn written_settings_read_back_the_same() {
+        let mut settings = Settings {
+            difficulty: Difficulty::Hard,
+            rules: RulesVariant::MisassignedCancelled,
+            first_player: Some(OpponentPlayer(OpponentTwo)),
+            seed: Some(7),
+            autoplay: AutoplaySpeed::Fast,
+            acknowledge_team_asks: false,
+            library_path: Some("/games/libstate.so".to_string()),
+            ..Settings::default()
+        };
+        settings.display.window_width = 80;
+        settings.display.window_height = 24;
+        settings.display.font = "big font.png".to_string();
+        settings.names.insert(TeammatePlayer(TeammateOne), "Tia Maria".to_string());
+
+        let text = to_string(&settings);
+        let (read, problems) = parse(&text);
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(read.difficulty, Difficulty::Hard);
+        assert_eq!(read.first_player, Some(OpponentPlayer(OpponentTwo)));
+        assert_eq!(read.display.font, "big font.png");
+        assert_eq!(read.names[&TeammatePlayer(TeammateOne)], "Tia Maria");
+        assert_eq!(to_string(&read), text);
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let (settings, problems) = parse("# seed = 1\n\n   \n  # indented = too\nseed = 3\n");
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(settings.seed, Some(3));
+    }
+
+    #[test]
+    fn an_unknown_key_is_reported_and_the_rest_still_read() {
+        let (settings, problems) = parse("colour = blue\nseed = 3\nno equals here");
+
+        assert_eq!(problems,
+                   vec!["line 1: unknown setting `colour`".to_string(),
+                        "line 3: expected `key = value` but found `no equals here`".to_string()]);
+        assert_eq!(settings.seed, Some(3));
+    }
+
+    #[test]
+    fn a_bad_value_says_what_was_expected_and_keeps_the_default() {
+        let (settings, problems) = parse("difficulty = impossible\nautoplay = slow");
+
+        assert_eq!(problems,
+                   vec!["line 1: `impossible` is not a valid difficulty, expected easy, normal \
+                         or hard"
+                                .to_string(),
+                        "line 2: `slow` is not a valid autoplay, expected instant, fast, normal \
+                         or step_by_step"
+                                .to_string()]);
+        assert_eq!(settings.difficulty, Settings::default().difficulty);
+        assert_eq!(settings.autoplay, Settings::default().autoplay);
+    }
+
+    #[test]
+    fn a_size_is_two_positive_numbers() {
+        assert_eq!(parse_dimensions(" 80 x 24 "), Ok((80, 24)));
+
+        for bad in ["104", "104x0", "-8x8", "10x20x30", "wide"].iter() {
+            assert_eq!(parse_dimensions(bad),
+                       Err(format!("`{}` is not a size like 104x36", bad)));
+        }
+
+        let (settings, problems) = parse("window_size = 104\nfont_size = 8x8");
+
+        assert_eq!(problems, vec!["line 1: `104` is not a size like 104x36".to_string()]);
+        assert_eq!(settings.display.window_width, 104);
+        assert_eq!(settings.display.font_width, 8);
+    }
+
+    #[test]
+    fn a_seat_has_to_be_one_at_the_table() {
+        let (settings, problems) = parse("first_player = Nobody\nname.Nobody = Al\nname.You = Me");
+
+        assert_eq!(problems,
+                   vec!["line 1: `Nobody` is not a valid first_player, expected random or a seat"
+                            .to_string(),
+                        "line 2: `Nobody` is not a seat".to_string()]);
+        assert_eq!(settings.first_player, None);
+        assert_eq!(settings.names[&TeammatePlayer(ThePlayer)], "Me");
+    }
+}
@@ -0,0 +1,716 @@
+//A backend that draws with ANSI escape codes and reads xterm mouse reports,
+//so the game runs in any terminal emulator, including over SSH.
+//
+//The `Platform` functions are plain `fn`s so everything they share lives in
+//a thread local. Only the cells that changed since the last refresh are sent.
+
+use libc;
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::mem;
+use std::panic;
+
+use common::*;
+
+use Backend;
+
+type Rgba = (u8, u8, u8, u8);
+
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    glyph: char,
+    foreground: Rgba,
+    background: Rgba,
+}
+
+const BLANK: Cell = Cell {
+    glyph: ' ',
+    foreground: (255, 255, 255, 255),
+    background: (0, 0, 0, 0),
+};
+
+struct Screen {
+    width: i32,
+    height: i32,
+    cells: Vec<Cell>,
+    //what the terminal is currently showing, None where we don't know
+    shown: Vec<Option<Cell>>,
+    foreground: Rgba,
+    background: Rgba,
+    layer: i32,
+    mouse: Point,
+    mouse_down: bool,
+    //bytes read from the terminal that don't make up a whole key yet
+    input: Vec<u8>,
+    original_mode: Option<libc::termios>,
+}
+
+impl Screen {
+    fn resize(&mut self, width: i32, height: i32) {
+        let len = (width * height) as usize;
+
+        self.width = width;
+        self.height = height;
+        self.cells = vec![BLANK; len];
+        self.shown = vec![None; len];
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+}
+
+thread_local! {
+    static SCREEN: RefCell<Screen> = const { RefCell::new(Screen {
+        width: 0,
+        height: 0,
+        cells: Vec::new(),
+        shown: Vec::new(),
+        foreground: BLANK.foreground,
+        background: BLANK.background,
+        layer: 0,
+        mouse: Point { x: 0, y: 0 },
+        mouse_down: false,
+        input: Vec::new(),
+        original_mode: None,
+    }) };
+}
+
+//alternate screen, hidden cursor, and mouse reports for every movement in
+//the SGR format so coordinates past column 223 work
+const ENTER: &str = "\x1b[?1049h\x1b[?25l\x1b[?1003h\x1b[?1006h";
+const LEAVE: &str = "\x1b[?1006l\x1b[?1003l\x1b[0m\x1b[?25h\x1b[?1049l";
+
+pub fn open() -> Result<Backend, String> {
+    let mut original_mode: libc::termios = unsafe { mem::zeroed() };
+    if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original_mode) } != 0 {
+        return Err("standard input is not a terminal".to_string());
+    }
+
+    let mut raw_mode = original_mode;
+    unsafe {
+        libc::cfmakeraw(&mut raw_mode);
+        libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw_mode);
+    }
+
+    let (width, height) = terminal_size();
+    SCREEN.with(|screen| {
+                    let mut screen = screen.borrow_mut();
+                    screen.resize(width, height);
+                    screen.original_mode = Some(original_mode);
+                });
+
+    //a panic would otherwise leave the terminal unusable
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+                                 close();
+                                 previous_hook(info);
+                             }));
+
+    write_out(ENTER);
+
+    Ok(Backend {
+           platform: Platform {
+               print_xy,
+               clear,
+               size,
+               pick,
+               mouse_position,
+               clicks,
+               key_pressed,
+               set_colors,
+               get_colors,
+               set_foreground,
+               get_foreground,
+               set_background,
+               get_background,
+               set_layer,
+               get_layer,
+           },
+           read_events,
+           refresh,
+           close,
+       })
+}
+
+fn close() {
+    let original_mode = SCREEN.with(|screen| screen.borrow_mut().original_mode.take());
+
+    //closing twice, say from the panic hook after a normal close, does nothing
+    if let Some(original_mode) = original_mode {
+        write_out(LEAVE);
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &original_mode);
+        }
+    }
+}
+
+fn terminal_size() -> (i32, i32) {
+    let mut size: libc::winsize = unsafe { mem::zeroed() };
+
+    if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 &&
+       size.ws_col > 0 && size.ws_row > 0 {
+        (size.ws_col as i32, size.ws_row as i32)
+    } else {
+        (80, 24)
+    }
+}
+
+fn write_out(text: &str) {
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+
+    let _ = handle.write_all(text.as_bytes());
+    let _ = handle.flush();
+}
+
+fn refresh() {
+    let output = SCREEN.with(|screen| {
+        let mut screen = screen.borrow_mut();
+        let mut output = String::new();
+        //where the terminal's cursor is after the last glyph we wrote
+        let mut cursor = None;
+        let mut colors = None;
+
+        for i in 0..screen.cells.len() {
+            let cell = screen.cells[i];
+            if screen.shown[i] == Some(cell) {
+                continue;
+            }
+
+            if cursor != Some(i) {
+                let x = i as i32 % screen.width;
+                let y = i as i32 / screen.width;
+                output.push_str(&format!("\x1b[{};{}H", y + 1, x + 1));
+            }
+
+            if colors != Some((cell.foreground, cell.background)) {
+                output.push_str(&color_code(38, cell.foreground));
+                output.push_str(&color_code(48, cell.background));
+                colors = Some((cell.foreground, cell.background));
+            }
+
+            output.push(cell.glyph);
+            screen.shown[i] = Some(cell);
+
+            //the terminal wraps to the next line on its own, except at the
+            //very end where it would scroll
+            cursor = if i + 1 < screen.cells.len() {
+                Some(i + 1)
+            } else {
+                None
+            };
+        }
+
+        output
+    });
+
+    if !output.is_empty() {
+        write_out(&output);
+    }
+}
+
+fn color_code(kind: u8, (red, green, blue, alpha): Rgba) -> String {
+    if alpha == 0 {
+        //the terminal's own colour
+        format!("\x1b[{}m", kind + 1)
+    } else {
+        format!("\x1b[{};2;{};{};{}m", kind, red, green, blue)
+    }
+}
+
+fn read_events(events: &mut Vec<Event>) {
+    let (width, height) = terminal_size();
+    let resized = SCREEN.with(|screen| {
+        let mut screen = screen.borrow_mut();
+
+        if screen.width != width || screen.height != height {
+            screen.resize(width, height);
+            true
+        } else {
+            false
+        }
+    });
+    if resized {
+        //whatever was on screen before is gone now
+        write_out("\x1b[0m\x1b[2J");
+        events.push(Event::Resize { width, height });
+    }
+
+    //waiting a little for the first byte keeps an idle game from spinning
+    read_input(10);
+    parse_waiting_input(events);
+
+    //the escape key and alt chords send what looks like the start of a
+    //longer sequence, so what they are is only known once nothing else follows
+    while SCREEN.with(|screen| !screen.borrow().input.is_empty()) {
+        if read_input(ESCAPE_WAIT_MS) {
+            parse_waiting_input(events);
+        } else {
+            let partial = SCREEN.with(|screen| mem::take(&mut screen.borrow_mut().input));
+
+            //anything else is an alt chord, or a sequence that was cut off
+            if partial == [0x1b] {
+                events.extend(key_events(KeyCode::Escape, false, false));
+            }
+        }
+    }
+}
+
+//how long to wait for the rest of an escape sequence, in milliseconds
+const ESCAPE_WAIT_MS: libc::c_int = 25;
+
+//adds whatever the terminal has sent to the input, waiting up to `timeout`
+//milliseconds for the first byte. Returns whether anything was read.
+fn read_input(mut timeout: libc::c_int) -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let mut buffer = [0u8; 256];
+    let mut read_any = false;
+
+    while unsafe { libc::poll(&mut fd, 1, timeout) } > 0 {
+        timeout = 0;
+        let count = unsafe {
+            libc::read(libc::STDIN_FILENO,
+                       buffer.as_mut_ptr() as *mut libc::c_void,
+                       buffer.len())
+        };
+        if count <= 0 {
+            break;
+        }
+        read_any = true;
+
+        SCREEN.with(|screen| {
+                        screen
+                            .borrow_mut()
+                            .input
+                            .extend_from_slice(&buffer[..count as usize])
+                    });
+    }
+
+    read_any
+}
+
+//turns the input into events, leaving any sequence that hasn't all arrived
+fn parse_waiting_input(events: &mut Vec<Event>) {
+    SCREEN.with(|screen| {
+        let mut screen = screen.borrow_mut();
+
+        let mut start = 0;
+        while start < screen.input.len() {
+            let (used, parsed) = parse_input(&screen.input[start..]);
+            if used == 0 {
+                //the rest of this sequence hasn't arrived yet
+                break;
+            }
+            start += used;
+
+            for event in parsed {
+                match event {
+                    Event::MouseMove { x, y } => screen.mouse = Point { x, y },
+                    Event::KeyPressed { key: KeyCode::MouseLeft, .. } => screen.mouse_down = true,
+                    Event::KeyReleased { key: KeyCode::MouseLeft, .. } => {
+                        screen.mouse_down = false
+                    }
+                    _ => {}
+                }
+
+                events.push(event);
+            }
+        }
+
+        screen.input.drain(..start);
+    });
+}
+
+//returns how many bytes were used, which is 0 if `input` ends partway
+//through an escape sequence, along with the events they stand for
+fn parse_input(input: &[u8]) -> (usize, Vec<Event>) {
+    match input[0] {
+        0x1b => parse_escape(input),
+        //without a signal handler ctrl-c is just another key, so treat it
+        //like closing the window
+        0x03 => (1, vec![Event::Close]),
+        b'\r' | b'\n' => (1, key_events(KeyCode::Enter, false, false)),
+        b'\t' => (1, key_events(KeyCode::Tab, false, false)),
+        0x7f | 0x08 => (1, key_events(KeyCode::Backspace, false, false)),
+        byte @ 0x01..=0x1a => {
+            (1, key_events(letter_key(byte - 1 + b'a'), true, false))
+        }
+        byte => {
+            let key = match byte {
+                b'a'..=b'z' => Some((letter_key(byte), false)),
+                b'A'..=b'Z' => Some((letter_key(byte.to_ascii_lowercase()), true)),
+                b'0'..=b'9' => Some((digit_key(byte), false)),
+                b' ' => Some((KeyCode::Space, false)),
+                b'.' => Some((KeyCode::Period, false)),
+                b',' => Some((KeyCode::Comma, false)),
+                b'-' => Some((KeyCode::Minus, false)),
+                b'=' => Some((KeyCode::Equals, false)),
+                b'/' => Some((KeyCode::Slash, false)),
+                b';' => Some((KeyCode::Semicolon, false)),
+                b'\'' => Some((KeyCode::Apostrophe, false)),
+                b'[' => Some((KeyCode::LeftBracket, false)),
+                b']' => Some((KeyCode::RightBracket, false)),
+                b'\\' => Some((KeyCode::Backslash, false)),
+                b'`' => Some((KeyCode::Grave, false)),
+                _ => None,
+            };
+
+            match key {
+                Some((key, shift)) => (1, key_events(key, false, shift)),
+                //the rest of a character we have no key for
+                None => (1, Vec::new()),
+            }
+        }
+    }
+}
+
+//a lone escape is left for `read_events` to decide on, since it can't
+//tell the escape key from the start of a sequence that is still arriving
+fn parse_escape(input: &[u8]) -> (usize, Vec<Event>) {
+    match input.get(1) {
+        None => (0, Vec::new()),
+        Some(&b'[') => parse_csi(input),
+        Some(&b'O') => {
+            match input.get(2) {
+                None => (0, Vec::new()),
+                Some(&b'P') => (3, key_events(KeyCode::F1, false, false)),
+                Some(&b'Q') => (3, key_events(KeyCode::F2, false, false)),
+                Some(&b'R') => (3, key_events(KeyCode::F3, false, false)),
+                Some(&b'S') => (3, key_events(KeyCode::F4, false, false)),
+                Some(_) => (3, Vec::new()),
+            }
+        }
+        //alt held down with a key that sends a sequence of its own, which
+        //is read next
+        Some(&0x1b) => (1, Vec::new()),
+        //alt held down with another key, which nothing in the game uses
+        Some(_) => (2, Vec::new()),
+    }
+}
+
+//sequences like `ESC [ 1 ; 2 C`
+fn parse_csi(input: &[u8]) -> (usize, Vec<Event>) {
+    let is_mouse = input.get(2) == Some(&b'<');
+    let parameters_start = if is_mouse { 3 } else { 2 };
+
+    let end = match input[parameters_start..]
+              .iter()
+              .position(|byte| (0x40..=0x7e).contains(byte)) {
+        Some(offset) => parameters_start + offset,
+        None => return (0, Vec::new()),
+    };
+    let used = end + 1;
+
+    let parameters: Vec<i32> = String::from_utf8_lossy(&input[parameters_start..end])
+        .split(';')
+        .map(|part| part.parse().unwrap_or(0))
+        .collect();
+    let parameter = |i: usize| parameters.get(i).cloned().unwrap_or(0);
+
+    if is_mouse {
+        return (used, mouse_events(parameter(0), parameter(1) - 1, parameter(2) - 1, input[end]));
+    }
+
+    //xterm adds one to a bitmask of held modifiers
+    let modifiers = parameter(1) - 1;
+    let shift = modifiers > 0 && modifiers & 1 != 0;
+    let ctrl = modifiers > 0 && modifiers & 4 != 0;
+
+    let key = match input[end] {
+        b'A' => Some(KeyCode::Up),
+        b'B' => Some(KeyCode::Down),
+        b'C' => Some(KeyCode::Right),
+        b'D' => Some(KeyCode::Left),
+        b'H' => Some(KeyCode::Home),
+        b'F' => Some(KeyCode::End),
+        b'Z' => return (used, key_events(KeyCode::Tab, false, true)),
+        b'~' => {
+            match parameter(0) {
+                1 | 7 => Some(KeyCode::Home),
+                2 => Some(KeyCode::Insert),
+                3 => Some(KeyCode::Delete),
+                4 | 8 => Some(KeyCode::End),
+                5 => Some(KeyCode::PageUp),
+                6 => Some(KeyCode::PageDown),
+                11 => Some(KeyCode::F1),
+                12 => Some(KeyCode::F2),
+                13 => Some(KeyCode::F3),
+                14 => Some(KeyCode::F4),
+                15 => Some(KeyCode::F5),
+                17 => Some(KeyCode::F6),
+                18 => Some(KeyCode::F7),
+                19 => Some(KeyCode::F8),
+                20 => Some(KeyCode::F9),
+                21 => Some(KeyCode::F10),
+                23 => Some(KeyCode::F11),
+                24 => Some(KeyCode::F12),
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    (used, key.map(|key| key_events(key, ctrl, shift)).unwrap_or_default())
+}
+
+//`button` is the SGR button code and `kind` is `M` for a press and `m` for
+//a release
+fn mouse_events(button: i32, x: i32, y: i32, kind: u8) -> Vec<Event> {
+    let mut events = vec![Event::MouseMove { x, y }];
+
+    if button & 64 != 0 {
+        events.push(Event::MouseScroll { delta: if button & 1 == 0 { -1 } else { 1 } });
+    } else if button & 32 == 0 {
+        let key = match button & 3 {
+            0 => KeyCode::MouseLeft,
+            1 => KeyCode::MouseMiddle,
+            _ => KeyCode::MouseRight,
+        };
+
+        events.push(if kind == b'M' {
+                        Event::KeyPressed {
+                            key,
+                            ctrl: false,
+                            shift: false,
+                        }
+                    } else {
+                        Event::KeyReleased {
+                            key,
+                            ctrl: false,
+                            shift: false,
+                        }
+                    });
+    }
+
+    events
+}
+
+//terminals only tell us a key was typed, so it is pressed and released at once
+fn key_events(key: KeyCode, ctrl: bool, shift: bool) -> Vec<Event> {
+    vec![Event::KeyPressed { key, ctrl, shift }, Event::KeyReleased { key, ctrl, shift }]
+}
+
+fn letter_key(byte: u8) -> KeyCode {
+    const LETTERS: [KeyCode; 26] = [KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E,
+                                    KeyCode::F, KeyCode::G, KeyCode::H, KeyCode::I, KeyCode::J,
+                                    KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N, KeyCode::O,
+                                    KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T,
+                                    KeyCode::U, KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y,
+                                    KeyCode::Z];
+
+    LETTERS[(byte - b'a') as usize]
+}
+
+fn digit_key(byte: u8) -> KeyCode {
+    const DIGITS: [KeyCode; 10] = [KeyCode::Row0, KeyCode::Row1, KeyCode::Row2, KeyCode::Row3,
+                                   KeyCode::Row4, KeyCode::Row5, KeyCode::Row6, KeyCode::Row7,
+                                   KeyCode::Row8, KeyCode::Row9];
+
+    DIGITS[(byte - b'0') as usize]
+}
+
+fn print_xy(x: i32, y: i32, text: &str) {
+    SCREEN.with(|screen| {
+        let mut screen = screen.borrow_mut();
+        let (foreground, background) = (screen.foreground, screen.background);
+
+        for (i, glyph) in text.chars().enumerate() {
+            if let Some(index) = screen.index(x + i as i32, y) {
+                screen.cells[index] = Cell {
+                    glyph,
+                    foreground,
+                    background,
+                };
+            }
+        }
+    });
+}
+
+fn clear(area: Option<Rect>) {
+    SCREEN.with(|screen| {
+        let mut screen = screen.borrow_mut();
+
+        let (left, top, width, height) = match area {
+            Some(rect) => (rect.top_left.x, rect.top_left.y, rect.size.width, rect.size.height),
+            None => (0, 0, screen.width, screen.height),
+        };
+
+        for y in top..top + height {
+            for x in left..left + width {
+                if let Some(index) = screen.index(x, y) {
+                    screen.cells[index] = BLANK;
+                }
+            }
+        }
+    });
+}
+
+fn size() -> Size {
+    SCREEN.with(|screen| {
+                    let screen = screen.borrow();
+
+                    Size {
+                        width: screen.width,
+                        height: screen.height,
+                    }
+                })
+}
+
+fn pick(point: Point, _index: i32) -> char {
+    SCREEN.with(|screen| {
+                    let screen = screen.borrow();
+
+                    screen
+                        .index(point.x, point.y)
+                        .map(|index| screen.cells[index].glyph)
+                        .unwrap_or(' ')
+                })
+}
+
+fn mouse_position() -> Point {
+    SCREEN.with(|screen| screen.borrow().mouse)
+}
+
+fn clicks() -> i32 {
+    if SCREEN.with(|screen| screen.borrow().mouse_down) {
+        1
+    } else {
+        0
+    }
+}
+
+//a terminal doesn't report keys being held, only the mouse button
+fn key_pressed(key: KeyCode) -> bool {
+    key == KeyCode::MouseLeft && SCREEN.with(|screen| screen.borrow().mouse_down)
+}
+
+fn to_rgba(color: Color) -> Rgba {
+    (color.red, color.green, color.blue, color.alpha)
+}
+
+fn from_rgba((red, green, blue, alpha): Rgba) -> Color {
+    Color {
+        red,
+        green,
+        blue,
+        alpha,
+    }
+}
+
+fn set_colors(foreground: Color, background: Color) {
+    set_foreground(foreground);
+    set_background(background);
+}
+
+fn get_colors() -> (Color, Color) {
+    (get_foreground(), get_background())
+}
+
+fn set_foreground(color: Color) {
+    SCREEN.with(|screen| screen.borrow_mut().foreground = to_rgba(color));
+}
+
+fn get_foreground() -> Color {
+    from_rgba(SCREEN.with(|screen| screen.borrow().foreground))
+}
+
+fn set_background(color: Color) {
+    SCREEN.with(|screen| screen.borrow_mut().background = to_rgba(color));
+}
+
+fn get_background() -> Color {
+    from_rgba(SCREEN.with(|screen| screen.borrow().background))
+}
+
+//there is only one layer of cells, but the game can still ask which it is on
+fn set_layer(layer: i32) {
+    SCREEN.with(|screen| screen.borrow_mut().layer = layer);
+}
+
+fn get_layer() -> i32 {
+    SCREEN.with(|screen| screen.borrow().layer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_bytes_are_keys() {
+        assert_eq!(parse_input(b"q"), (1, key_events(KeyCode::Q, false, false)));
+        assert_eq!(parse_input(b"Q"), (1, key_events(KeyCode::Q, false, true)));
+        assert_eq!(parse_input(b"7"), (1, key_events(KeyCode::Row7, false, false)));
+        assert_eq!(parse_input(b"\r"), (1, key_events(KeyCode::Enter, false, false)));
+        assert_eq!(parse_input(b"\x7f"), (1, key_events(KeyCode::Backspace, false, false)));
+        assert_eq!(parse_input(b"\x01"), (1, key_events(KeyCode::A, true, false)));
+        assert_eq!(parse_input(b"\x03"), (1, vec![Event::Close]));
+        //the first byte of ♣, and the rest of it
+        assert_eq!(parse_input("♣".as_bytes()), (1, Vec::new()));
+    }
+
+    #[test]
+    fn a_lone_escape_waits_to_see_what_follows() {
+        assert_eq!(parse_input(b"\x1b"), (0, Vec::new()));
+        assert_eq!(parse_input(b"\x1bO"), (0, Vec::new()));
+        assert_eq!(parse_input(b"\x1b[1;5"), (0, Vec::new()));
+    }
+
+    #[test]
+    fn an_alt_chord_is_not_the_escape_key() {
+        assert_eq!(parse_input(b"\x1bq"), (2, Vec::new()));
+        assert_eq!(parse_input(b"\x1b\x1b[A"), (1, Vec::new()));
+        assert_eq!(parse_input(b"\x1b[A"), (3, key_events(KeyCode::Up, false, false)));
+    }
+
+    #[test]
+    fn csi_sequences_are_keys_with_their_modifiers() {
+        assert_eq!(parse_csi(b"\x1b[D"), (3, key_events(KeyCode::Left, false, false)));
+        assert_eq!(parse_csi(b"\x1b[1;5C"), (6, key_events(KeyCode::Right, true, false)));
+        assert_eq!(parse_csi(b"\x1b[1;2Hq"), (6, key_events(KeyCode::Home, false, true)));
+        assert_eq!(parse_csi(b"\x1b[3~"), (4, key_events(KeyCode::Delete, false, false)));
+        assert_eq!(parse_csi(b"\x1b[24~"), (5, key_events(KeyCode::F12, false, false)));
+        assert_eq!(parse_csi(b"\x1b[Z"), (3, key_events(KeyCode::Tab, false, true)));
+        assert_eq!(parse_csi(b"\x1b[99~"), (5, Vec::new()));
+        assert_eq!(parse_input(b"\x1bOP"), (3, key_events(KeyCode::F1, false, false)));
+    }
+
+    #[test]
+    fn mouse_reports_move_the_mouse_then_press_release_or_scroll() {
+        let press = Event::KeyPressed {
+            key: KeyCode::MouseLeft,
+            ctrl: false,
+            shift: false,
+        };
+        let release = Event::KeyReleased {
+            key: KeyCode::MouseLeft,
+            ctrl: false,
+            shift: false,
+        };
+
+        assert_eq!(parse_input(b"\x1b[<0;10;5M"),
+                   (10, vec![Event::MouseMove { x: 9, y: 4 }, press]));
+        assert_eq!(parse_input(b"\x1b[<0;250;5m"),
+                   (11, vec![Event::MouseMove { x: 249, y: 4 }, release]));
+        //held buttons while moving are only a move
+        assert_eq!(parse_input(b"\x1b[<35;3;4M"),
+                   (10, vec![Event::MouseMove { x: 2, y: 3 }]));
+        assert_eq!(mouse_events(65, 0, 0, b'M'),
+                   vec![Event::MouseMove { x: 0, y: 0 }, Event::MouseScroll { delta: 1 }]);
+        assert_eq!(mouse_events(2, 0, 0, b'M')[1],
+                   Event::KeyPressed {
+                       key: KeyCode::MouseRight,
+                       ctrl: false,
+                       shift: false,
+                   });
+    }
+}
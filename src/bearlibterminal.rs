@@ -0,0 +1,320 @@
+//The original backend, a window drawn by BearLibTerminal.
+
+use bear_lib_terminal::terminal::{self, config, KeyCode, state};
+use bear_lib_terminal::Color;
+use bear_lib_terminal::geometry::{Point, Rect, Size};
+
+use common;
+use common::*;
+
+use Backend;
+
+pub fn open(display: &DisplaySettings) -> Result<Backend, String> {
+    terminal::open("____", display.window_width as u32, display.window_height as u32);
+    terminal::set(config::Window::empty().resizeable(true));
+    terminal::set(vec![config::InputFilter::Group {
+                           group: config::InputFilterGroup::Keyboard,
+                           both: false,
+                       },
+                       config::InputFilter::Group {
+                           group: config::InputFilterGroup::Mouse,
+                           both: false,
+                       }]);
+    use bear_lib_terminal::terminal::config::font;
+    terminal::set(font::bitmap(font::Origin::Offset('☐'), &display.font)
+                      .size(Size::new(display.font_width, display.font_height)));
+
+    //if this isn't set to something explicitly `get_foreground`
+    //will return 0 (transparent black) messing up code that
+    //reads the foreground then sets a different one then sets
+    // it back to what it was before.
+    set_foreground(common::Color {
+                       red: 255,
+                       green: 255,
+                       blue: 255,
+                       alpha: 255,
+                   });
+
+    Ok(Backend {
+           platform: Platform {
+               print_xy: terminal::print_xy,
+               clear,
+               size,
+               pick,
+               mouse_position,
+               clicks: terminal::state::mouse::clicks,
+               key_pressed,
+               set_colors,
+               get_colors,
+               set_layer: terminal::layer,
+               get_layer: terminal::state::layer,
+               set_foreground,
+               get_foreground,
+               set_background,
+               get_background,
+           },
+           read_events,
+           refresh: terminal::refresh,
+           close: terminal::close,
+       })
+}
+
+fn read_events(events: &mut Vec<common::Event>) {
+    while let Some(event) = terminal::read_event() {
+        events.push(from_bear_event(event));
+    }
+}
+
+fn clear(area: Option<common::Rect>) {
+    terminal::clear(area.map(to_bear_rect));
+}
+
+fn size() -> common::Size {
+    let size = state::size();
+
+    common::Size {
+        width: size.width,
+        height: size.height,
+    }
+}
+
+fn mouse_position() -> common::Point {
+    from_bear_point(state::mouse::position())
+}
+
+//Note: index selects a cell in *a single* layer, in case you have composition mode on.
+//To pick on different layers, set the current layer then pick.
+fn pick(point: common::Point, index: i32) -> char {
+    terminal::pick(to_bear_point(point), index)
+}
+
+fn key_pressed(key: common::KeyCode) -> bool {
+    terminal::state::key_pressed(to_bear_key(key))
+}
+
+fn set_colors(fg: common::Color, bg: common::Color) {
+    terminal::set_colors(to_bear_color(fg), to_bear_color(bg));
+}
+
+fn get_colors() -> (common::Color, common::Color) {
+    (get_foreground(), get_background())
+}
+
+fn set_foreground(fg: common::Color) {
+    terminal::set_foreground(to_bear_color(fg));
+}
+fn get_foreground() -> common::Color {
+    from_bear_color(terminal::state::foreground())
+}
+fn set_background(bg: common::Color) {
+    terminal::set_background(to_bear_color(bg))
+}
+fn get_background() -> common::Color {
+    from_bear_color(terminal::state::background())
+}
+
+//Everything that crosses between BearLibTerminal and the rest of the game
+//goes through these, field by field, so that if a bear-lib-terminal upgrade
+//changes one of its types this stops compiling rather than scrambling input.
+//They use struct literals rather than `new` since `new` panics on negative
+//values and the mouse can be outside the window.
+
+fn to_bear_point(point: common::Point) -> Point {
+    Point {
+        x: point.x,
+        y: point.y,
+    }
+}
+
+fn from_bear_point(point: Point) -> common::Point {
+    common::Point {
+        x: point.x,
+        y: point.y,
+    }
+}
+
+fn to_bear_rect(rect: common::Rect) -> Rect {
+    Rect {
+        top_left: to_bear_point(rect.top_left),
+        top_right: to_bear_point(rect.top_right),
+        bottom_right: to_bear_point(rect.bottom_right),
+        bottom_left: to_bear_point(rect.bottom_left),
+        size: Size {
+            width: rect.size.width,
+            height: rect.size.height,
+        },
+    }
+}
+
+fn to_bear_color(color: common::Color) -> Color {
+    Color {
+        red: color.red,
+        green: color.green,
+        blue: color.blue,
+        alpha: color.alpha,
+    }
+}
+
+fn from_bear_color(color: Color) -> common::Color {
+    common::Color {
+        red: color.red,
+        green: color.green,
+        blue: color.blue,
+        alpha: color.alpha,
+    }
+}
+
+fn from_bear_event(event: terminal::Event) -> common::Event {
+    match event {
+        terminal::Event::Close => common::Event::Close,
+        terminal::Event::Resize { width, height } => common::Event::Resize { width, height },
+        terminal::Event::MouseMove { x, y } => common::Event::MouseMove { x, y },
+        terminal::Event::MouseScroll { delta } => common::Event::MouseScroll { delta },
+        terminal::Event::KeyPressed { key, ctrl, shift } => {
+            common::Event::KeyPressed {
+                key: from_bear_key(key),
+                ctrl,
+                shift,
+            }
+        }
+        terminal::Event::KeyReleased { key, ctrl, shift } => {
+            common::Event::KeyReleased {
+                key: from_bear_key(key),
+                ctrl,
+                shift,
+            }
+        }
+        terminal::Event::ShiftPressed => common::Event::ShiftPressed,
+        terminal::Event::ShiftReleased => common::Event::ShiftReleased,
+        terminal::Event::ControlPressed => common::Event::ControlPressed,
+        terminal::Event::ControlReleased => common::Event::ControlReleased,
+    }
+}
+
+//only the tests need to go this way
+#[cfg(test)]
+fn to_bear_event(event: common::Event) -> terminal::Event {
+    match event {
+        common::Event::Close => terminal::Event::Close,
+        common::Event::Resize { width, height } => terminal::Event::Resize { width, height },
+        common::Event::MouseMove { x, y } => terminal::Event::MouseMove { x, y },
+        common::Event::MouseScroll { delta } => terminal::Event::MouseScroll { delta },
+        common::Event::KeyPressed { key, ctrl, shift } => {
+            terminal::Event::KeyPressed {
+                key: to_bear_key(key),
+                ctrl,
+                shift,
+            }
+        }
+        common::Event::KeyReleased { key, ctrl, shift } => {
+            terminal::Event::KeyReleased {
+                key: to_bear_key(key),
+                ctrl,
+                shift,
+            }
+        }
+        common::Event::ShiftPressed => terminal::Event::ShiftPressed,
+        common::Event::ShiftReleased => terminal::Event::ShiftReleased,
+        common::Event::ControlPressed => terminal::Event::ControlPressed,
+        common::Event::ControlReleased => terminal::Event::ControlReleased,
+    }
+}
+
+//both matches are exhaustive, so a key added to or removed from either side
+//is a compile error
+macro_rules! key_codes {
+    ($($key:ident),* $(,)*) => {
+        fn to_bear_key(key: common::KeyCode) -> KeyCode {
+            match key {
+                $(common::KeyCode::$key => KeyCode::$key,)*
+            }
+        }
+
+        fn from_bear_key(key: KeyCode) -> common::KeyCode {
+            match key {
+                $(KeyCode::$key => common::KeyCode::$key,)*
+            }
+        }
+
+        #[cfg(test)]
+        const ALL_KEY_CODES: &[common::KeyCode] = &[$(common::KeyCode::$key,)*];
+    }
+}
+
+key_codes!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+           Row1, Row2, Row3, Row4, Row5, Row6, Row7, Row8, Row9, Row0,
+           Grave, Minus, Equals, LeftBracket, RightBracket, Backslash, Semicolon, Apostrophe,
+           Comma, Period, Slash,
+           F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+           Enter, Escape, Backspace, Tab, Space, Pause, Insert, Home, PageUp, Delete, End,
+           PageDown, Right, Left, Down, Up,
+           NumDivide, NumMultiply, NumMinus, NumPlus, NumEnter, NumPeriod,
+           Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9, Num0,
+           MouseLeft, MouseRight, MouseMiddle, MouseFourth, MouseFifth);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_key_code_round_trips() {
+        for &key in ALL_KEY_CODES {
+            assert_eq!(from_bear_key(to_bear_key(key)), key);
+            assert_eq!(format!("{:?}", to_bear_key(key)), format!("{:?}", key));
+        }
+    }
+
+    #[test]
+    fn no_two_key_codes_share_a_bear_key() {
+        for (i, &key) in ALL_KEY_CODES.iter().enumerate() {
+            for &other in &ALL_KEY_CODES[i + 1..] {
+                assert_ne!(key, other);
+                assert_ne!(to_bear_key(key), to_bear_key(other));
+            }
+        }
+    }
+
+    #[test]
+    fn every_event_round_trips() {
+        let mut events = vec![common::Event::Close,
+                              common::Event::Resize {
+                                  width: 104,
+                                  height: 36,
+                              },
+                              common::Event::MouseMove { x: -3, y: 70 },
+                              common::Event::MouseScroll { delta: -2 },
+                              common::Event::ShiftPressed,
+                              common::Event::ShiftReleased,
+                              common::Event::ControlPressed,
+                              common::Event::ControlReleased];
+
+        for &key in ALL_KEY_CODES {
+            for &(ctrl, shift) in &[(false, false), (true, false), (false, true), (true, true)] {
+                events.push(common::Event::KeyPressed { key, ctrl, shift });
+                events.push(common::Event::KeyReleased { key, ctrl, shift });
+            }
+        }
+
+        for event in events {
+            assert_eq!(from_bear_event(to_bear_event(event)), event);
+            assert_eq!(format!("{:?}", to_bear_event(event)), format!("{:?}", event));
+        }
+    }
+
+    #[test]
+    fn geometry_and_colors_keep_their_values() {
+        let point = common::Point { x: 12, y: -5 };
+        assert_eq!(from_bear_point(to_bear_point(point)), point);
+
+        let rect = to_bear_rect(common::Rect::from_values(1, 2, 30, 40));
+        assert_eq!(rect, Rect::from_values(1, 2, 30, 40));
+
+        let color = from_bear_color(to_bear_color(common::Color {
+                                                      red: 1,
+                                                      green: 2,
+                                                      blue: 3,
+                                                      alpha: 4,
+                                                  }));
+        assert_eq!((color.red, color.green, color.blue, color.alpha), (1, 2, 3, 4));
+    }
+}
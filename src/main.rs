@@ -1,50 +1,99 @@
 
+#[cfg(feature = "bear-lib-terminal")]
 extern crate bear_lib_terminal;
 extern crate common;
 
 #[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "hot-reload")]
 extern crate libloading;
-#[cfg(not(unix))]
+#[cfg(not(feature = "hot-reload"))]
 extern crate state_manipulation;
 
-#[cfg(unix)]
+#[cfg(feature = "hot-reload")]
 use libloading::Library;
 
-use bear_lib_terminal::terminal::{self, config, Event, KeyCode, state};
-use bear_lib_terminal::Color;
-use bear_lib_terminal::geometry::{Point, Rect, Size};
-
-use std::mem;
+use std::env;
 
 use common::*;
 
 #[cfg(unix)]
-const LIB_PATH: &'static str = "./target/debug/libstate_manipulation.so";
-#[cfg(not(unix))]
-const LIB_PATH: &'static str = "state_manipulation.dll";
+mod ansi;
+#[cfg(feature = "bear-lib-terminal")]
+mod bearlibterminal;
 
-#[cfg(unix)]
+#[cfg(feature = "hot-reload")]
 struct Application {
     library: Library,
 }
-#[cfg(not(unix))]
+#[cfg(not(feature = "hot-reload"))]
 struct Application {}
 
-#[cfg(unix)]
+//the state is only ever touched through the library that made it, see
+//`new_state` in state_manipulation
+#[cfg(feature = "hot-reload")]
 impl Application {
-    fn new() -> Self {
-        let library = Library::new(LIB_PATH).unwrap_or_else(|error| panic!("{}", error));
+    fn new(lib_path: &str) -> Self {
+        let library = Library::new(lib_path).unwrap_or_else(|error| panic!("{}", error));
 
         Application { library: library }
     }
 
-    fn new_state(&self, size: common::Size) -> State {
+    fn new_state(&self, size: common::Size) -> Box<State> {
         unsafe {
-            let f = self.library.get::<fn(common::Size) -> State>(b"new_state\0").unwrap();
+            let f = self.library.get::<fn(common::Size) -> Box<State>>(b"new_state\0").unwrap();
             f(size)
         }
     }
 
+    fn new_state_with_notice(&self, size: common::Size, notice: &str) -> Box<State> {
+        unsafe {
+            let f = self.library
+                .get::<fn(common::Size, &str) -> Box<State>>(b"new_state_with_notice\0")
+                .unwrap();
+            f(size, notice)
+        }
+    }
+
+    fn new_replay_state(&self, size: common::Size, path: &str) -> Box<State> {
+        unsafe {
+            let f = self.library
+                .get::<fn(common::Size, &str) -> Box<State>>(b"new_replay_state\0")
+                .unwrap();
+            f(size, path)
+        }
+    }
+
+    fn state_version(&self) -> u32 {
+        unsafe {
+            let f = self.library.get::<fn() -> u32>(b"state_version\0").unwrap();
+            f()
+        }
+    }
+
+    fn save_state(&self, state: &State) -> String {
+        unsafe {
+            let f = self.library.get::<fn(&State) -> String>(b"save_state\0").unwrap();
+            f(state)
+        }
+    }
+
+    fn load_state(&self, text: &str, size: common::Size) -> Result<Box<State>, String> {
+        unsafe {
+            let f = self.library
+                .get::<fn(&str, common::Size) -> Result<Box<State>, String>>(b"load_state\0")
+                .unwrap();
+            f(text, size)
+        }
+    }
+
+    fn drop_state(&self, state: Box<State>) {
+        unsafe {
+            let f = self.library.get::<fn(Box<State>)>(b"drop_state\0").unwrap();
+            f(state)
+        }
+    }
+
     fn update_and_render(&self,
                          platform: &Platform,
                          state: &mut State,
@@ -58,158 +107,201 @@ impl Application {
         }
     }
 }
-#[cfg(not(unix))]
+#[cfg(not(feature = "hot-reload"))]
 impl Application {
-    fn new() -> Self {
+    fn new(_lib_path: &str) -> Self {
         Application {}
     }
 
-    fn new_state(&self, size: common::Size) -> State {
+    fn new_state(&self, size: common::Size) -> Box<State> {
         state_manipulation::new_state(size)
     }
 
+    fn new_replay_state(&self, size: common::Size, path: &str) -> Box<State> {
+        state_manipulation::new_replay_state(size, path)
+    }
+
+    fn drop_state(&self, state: Box<State>) {
+        state_manipulation::drop_state(state)
+    }
+
     fn update_and_render(&self,
                          platform: &Platform,
                          state: &mut State,
                          events: &Vec<Event>)
                          -> bool {
-        let mut new_events: Vec<common::Event> =
-            unsafe { events.iter().map(|a| mem::transmute::<Event, common::Event>(*a)).collect() };
-        state_manipulation::update_and_render(platform, state, &mut new_events)
+        let mut events = events.clone();
+        state_manipulation::update_and_render(platform, state, &mut events)
     }
 }
 
+//swaps in a rebuilt library, carrying the game over as text. If the new
+//library can't read what the old one wrote, the player gets a new game and a
+//note on the title screen saying why.
+#[cfg(feature = "hot-reload")]
+fn reload(app: Application,
+          state: Box<State>,
+          lib_path: &str,
+          size: common::Size)
+          -> (Application, Box<State>) {
+    let text = app.save_state(&state);
+    let old_version = app.state_version();
+
+    app.drop_state(state);
+    drop(app);
+
+    let app = Application::new(lib_path);
+    let new_version = app.state_version();
+
+    let loaded = if new_version < old_version {
+        Err(format!("the game was saved in format {} but the new code only reads up to {}",
+                    old_version,
+                    new_version))
+    } else {
+        app.load_state(&text, size)
+    };
+
+    let state = match loaded {
+        Ok(state) => state,
+        Err(problem) => {
+            app.new_state_with_notice(size, &format!("Reloaded with a new game, {}", problem))
+        }
+    };
+
+    (app, state)
+}
+
+//what the main loop needs from a backend, on top of the `Platform` it hands
+//to the game
+pub struct Backend {
+    platform: Platform,
+    read_events: fn(&mut Vec<Event>),
+    refresh: fn(),
+    close: fn(),
+}
+
 fn main() {
-    terminal::open("____", 80, 30);
-    terminal::set(config::Window::empty().resizeable(true));
-    terminal::set(vec![config::InputFilter::Group {
-                           group: config::InputFilterGroup::Keyboard,
-                           both: false,
-                       },
-                       config::InputFilter::Group {
-                           group: config::InputFilterGroup::Mouse,
-                           both: false,
-                       }]);
-    use bear_lib_terminal::terminal::config::font;
-    terminal::set(font::bitmap(font::Origin::Offset('☐'), "such_graphics.png")
-                      .size(Size::new(16, 16)));
-
-
-    let mut app = Application::new();
-
-    let mut state = app.new_state(size());
-
-    let mut last_modified = std::fs::metadata(LIB_PATH).unwrap().modified().unwrap();
-
-    let platform = Platform {
-        print_xy: terminal::print_xy,
-        clear: clear,
-        size: size,
-        pick: pick,
-        mouse_position: mouse_position,
-        clicks: terminal::state::mouse::clicks,
-        key_pressed: key_pressed,
-        set_colors: set_colors,
-        get_colors: get_colors,
-        set_layer: terminal::layer,
-        get_layer: terminal::state::layer,
-        set_foreground: set_foreground,
-        get_foreground: get_foreground,
-        set_background: set_background,
-        get_background: get_background,
+    //the game loads these again itself, we just need the display ones here
+    let config_dir = settings_file::config_dir();
+    let (settings, problems) = settings_file::load(config_dir.as_deref());
+    for problem in problems {
+        println!("settings: {}", problem);
+    }
+
+    let backend = match open_backend(&settings.display) {
+        Ok(backend) => backend,
+        Err(problem) => {
+            println!("{}", problem);
+            return;
+        }
     };
+    let platform = &backend.platform;
+
+    let lib_path = settings.library_path.unwrap_or_else(default_library_path);
 
-    //if this isn't set to something explicitly `get_foreground`
-    //will return 0 (transparent black) messing up code that
-    //reads the foreground then sets a different one then sets
-    // it back to what it was before.
-    set_foreground(common::Color {
-                       red: 255,
-                       green: 255,
-                       blue: 255,
-                       alpha: 255,
-                   });
+    //only a reload swaps in a new one
+    #[cfg(feature = "hot-reload")]
+    let mut app = Application::new(&lib_path);
+    #[cfg(not(feature = "hot-reload"))]
+    let app = Application::new(&lib_path);
 
+    let mut state = match replay_argument() {
+        Some(path) => app.new_replay_state((platform.size)(), &path),
+        None => app.new_state((platform.size)()),
+    };
+
+    #[cfg(feature = "hot-reload")]
+    let mut last_modified = modified_time(&lib_path);
 
     let mut events = Vec::new();
 
-    app.update_and_render(&platform, &mut state, &mut events);
+    app.update_and_render(platform, &mut state, &events);
 
-    terminal::refresh();
+    (backend.refresh)();
 
     loop {
         events.clear();
 
-        while let Some(event) = terminal::read_event() {
-            events.push(event);
-        }
+        (backend.read_events)(&mut events);
 
-        terminal::clear(None);
+        (platform.clear)(None);
 
-        if app.update_and_render(&platform, &mut state, &mut events) {
+        if app.update_and_render(platform, &mut state, &events) {
             //quit requested
             break;
         }
 
-        terminal::refresh();
+        (backend.refresh)();
 
-        if cfg!(unix) {
-            if let Ok(Ok(modified)) = std::fs::metadata(LIB_PATH).map(|m| m.modified()) {
-                if modified > last_modified {
-                    drop(app);
-                    app = Application::new();
-                    last_modified = modified;
-                }
+        #[cfg(feature = "hot-reload")]
+        {
+            let modified = modified_time(&lib_path);
+            if modified > last_modified {
+                let (new_app, new_state) = reload(app, state, &lib_path, (platform.size)());
+                app = new_app;
+                state = new_state;
+                last_modified = modified;
             }
         }
-
     }
 
-    terminal::close();
-}
+    app.drop_state(state);
 
-fn clear(area: Option<common::Rect>) {
-    unsafe { terminal::clear(mem::transmute::<Option<common::Rect>, Option<Rect>>(area)) };
+    (backend.close)();
 }
 
-fn size() -> common::Size {
-    unsafe { mem::transmute::<Size, common::Size>(state::size()) }
-}
+//`--replay <path>` opens a game record straight away
+fn replay_argument() -> Option<String> {
+    let mut args = env::args().skip(1);
 
-fn mouse_position() -> common::Point {
-    unsafe { mem::transmute::<Point, common::Point>(state::mouse::position()) }
-}
+    args.find(|arg| arg == "--replay")?;
 
-//Note: index selects a cell in *a single* layer, in case you have composition mode on.
-//To pick on different layers, set the current layer then pick.
-fn pick(point: common::Point, index: i32) -> char {
-    terminal::pick(unsafe { mem::transmute::<common::Point, Point>(point) },
-                   index)
+    args.next()
 }
 
-fn key_pressed(key: common::KeyCode) -> bool {
-    terminal::state::key_pressed(unsafe { mem::transmute::<common::KeyCode, KeyCode>(key) })
+//the game code is built next to the executable, as `cargo build` does
+fn default_library_path() -> String {
+    let file_name = format!("{}state_manipulation{}",
+                            env::consts::DLL_PREFIX,
+                            env::consts::DLL_SUFFIX);
+
+    env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|folder| folder.join(&file_name)))
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or(file_name)
 }
 
-fn set_colors(fg: common::Color, bg: common::Color) {
-    terminal::set_colors(unsafe { mem::transmute::<common::Color, Color>(fg) },
-                         unsafe { mem::transmute::<common::Color, Color>(bg) });
-
+//None while the library is missing, say part way through a rebuild
+#[cfg(feature = "hot-reload")]
+fn modified_time(lib_path: &str) -> Option<std::time::SystemTime> {
+    std::fs::metadata(lib_path).and_then(|metadata| metadata.modified()).ok()
 }
 
-fn get_colors() -> (common::Color, common::Color) {
-    (get_foreground(), get_background())
+//`--ansi` picks the terminal backend, otherwise we use a window when this
+//build has one
+fn open_backend(display: &DisplaySettings) -> Result<Backend, String> {
+    if env::args().skip(1).any(|arg| arg == "--ansi") || !cfg!(feature = "bear-lib-terminal") {
+        open_ansi()
+    } else {
+        open_window(display)
+    }
 }
 
-fn set_foreground(fg: common::Color) {
-    terminal::set_foreground(unsafe { mem::transmute::<common::Color, Color>(fg) });
+#[cfg(unix)]
+fn open_ansi() -> Result<Backend, String> {
+    ansi::open()
 }
-fn get_foreground() -> common::Color {
-    unsafe { mem::transmute::<Color, common::Color>(terminal::state::foreground()) }
+#[cfg(not(unix))]
+fn open_ansi() -> Result<Backend, String> {
+    Err("The ANSI terminal backend is only available on unix".to_string())
 }
-fn set_background(bg: common::Color) {
-    terminal::set_background(unsafe { mem::transmute::<common::Color, Color>(bg) })
+
+#[cfg(feature = "bear-lib-terminal")]
+fn open_window(display: &DisplaySettings) -> Result<Backend, String> {
+    bearlibterminal::open(display)
 }
-fn get_background() -> common::Color {
-    unsafe { mem::transmute::<Color, common::Color>(terminal::state::background()) }
+#[cfg(not(feature = "bear-lib-terminal"))]
+fn open_window(_display: &DisplaySettings) -> Result<Backend, String> {
+    Err("This build does not include BearLibTerminal, try --ansi".to_string())
 }
@@ -2,8 +2,6 @@ extern crate rand;
 extern crate common;
 
 use common::*;
-use common::Suit::*;
-use common::Value::*;
 use common::MenuState::*;
 use common::Declaration::*;
 use common::DeclarationInfo::*;
@@ -14,43 +12,157 @@ use common::AskVector::*;
 use common::SubSuit::*;
 use common::Fact::*;
 use common::ModelCard::*;
+use common::HistoryEntry::*;
+use common::SidePanel::*;
+use common::HandOrder::*;
+use common::Difficulty::*;
+use common::RulesVariant::*;
+use common::Personality::*;
 use common::AllValues;
+use common::settings_file;
+use common::save_file;
+use common::game_record::{self, GameRecord};
 
-use rand::{StdRng, SeedableRng, Rng};
+use rand::Rng;
 
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[cfg(test)]
+mod ui_script;
+
+//The state is boxed so the executable only ever holds a pointer to it. That
+//way it doesn't need to know the layout of a `State`, which can change when
+//this library is rebuilt, and it hands the box back to `drop_state` rather
+//than dropping it itself.
 
 //NOTE(Ryan1729): debug_assertions only appears to work correctly when the
 //crate is not a dylib. Assuming you make this crate *not* a dylib on release,
 //these configs should work
 #[cfg(debug_assertions)]
 #[no_mangle]
-pub fn new_state(size: Size) -> State {
+pub fn new_state(size: Size) -> Box<State> {
     //skip the title screen
-    println!("debug on");
-
-    let seed: &[_] = &[42];
-    let mut rng: StdRng = SeedableRng::from_seed(seed);
-
-    make_state(size, false, rng)
+    Box::new(starting_state(size, settings_file::config_dir()))
 }
 #[cfg(not(debug_assertions))]
 #[no_mangle]
-pub fn new_state(size: Size) -> State {
+pub fn new_state(size: Size) -> Box<State> {
     //show the title screen
+    let mut state = starting_state(size, settings_file::config_dir());
+    state.title_screen = true;
+
+    Box::new(state)
+}
+
+//the title screen with a message explaining why the player is seeing it
+#[no_mangle]
+pub fn new_state_with_notice(size: Size, notice: &str) -> Box<State> {
+    let mut state = starting_state(size, settings_file::config_dir());
+    state.title_screen = true;
+    state.notice = Some(notice.to_string());
+
+    Box::new(state)
+}
+
+//a replay of the record at `path`, or the title screen saying why it can't be
+#[no_mangle]
+pub fn new_replay_state(size: Size, path: &str) -> Box<State> {
+    Box::new(open_replay(starting_state(size, settings_file::config_dir()), size, path))
+}
+
+//`state` showing the record at `path`, or the title screen saying why it can't
+fn open_replay(mut state: State, size: Size, path: &str) -> State {
+    let started = game_record::load(Path::new(path))
+        .and_then(|record| start_replay(&mut state, size, record));
+
+    if let Err(problem) = started {
+        state.title_screen = true;
+        state.notice = Some(problem);
+    }
+
+    state
+}
+
+//the version of the text `save_state` writes
+#[no_mangle]
+pub fn state_version() -> u32 {
+    save_file::VERSION
+}
+
+#[no_mangle]
+pub fn save_state(state: &State) -> String {
+    save_file::write(state)
+}
+
+//the text from a `save_state`, possibly from an older build of this library
+#[no_mangle]
+pub fn load_state(text: &str, size: Size) -> Result<Box<State>, String> {
+    save_file::read(text, starting_state(size, settings_file::config_dir())).map(Box::new)
+}
+
+#[no_mangle]
+pub fn drop_state(state: Box<State>) {
+    drop(state);
+}
+
+//a game with the settings kept in `config_dir`, which is where it keeps
+//everything else too
+fn starting_state(size: Size, config_dir: Option<PathBuf>) -> State {
+    let (settings, problems) = settings_file::load(config_dir.as_deref());
+
+    let mut state = new_game(size, settings);
+    state.settings_problems = problems;
+    state.config_dir = config_dir;
+
+    state
+}
+
+//used when the settings don't specify a seed
+#[cfg(debug_assertions)]
+fn fallback_seed() -> usize {
+    42
+}
+#[cfg(not(debug_assertions))]
+fn fallback_seed() -> usize {
     let timestamp = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
         .map(|dur| dur.as_secs())
         .unwrap_or(42);
 
-    println!("{}", timestamp);
-    let seed: &[_] = &[timestamp as usize];
-    let rng: StdRng = SeedableRng::from_seed(seed);
+    timestamp as usize
+}
+
+fn new_game(size: Size, settings: Settings) -> State {
+    let rng = GameRng::new(settings.seed.unwrap_or_else(fallback_seed));
 
-    make_state(size, true, rng)
+    make_state(size, false, rng, settings)
+}
+
+//every seat, including the player's, is played by a CPU
+fn new_spectated_game(size: Size, settings: Settings) -> State {
+    let mut state = new_game(size, settings);
+    state.spectating = true;
+    state.autopilot = true;
+
+    state
+}
+
+//starts another game of the same kind
+fn restart(state: &mut State, size: Size) {
+    let settings = state.settings.clone();
+    let config_dir = state.config_dir.take();
+
+    *state = if state.spectating {
+        new_spectated_game(size, settings)
+    } else {
+        new_game(size, settings)
+    };
+    state.config_dir = config_dir;
 }
 
-fn make_state(size: Size, title_screen: bool, mut rng: StdRng) -> State {
+fn make_state(size: Size, title_screen: bool, mut rng: GameRng, settings: Settings) -> State {
     let mut deck = shuffled_deck(&mut rng);
 
     let mut player = Vec::new();
@@ -75,9 +187,12 @@ fn make_state(size: Size, title_screen: bool, mut rng: StdRng) -> State {
     let opponent_1_memory = new_memory(OpponentPlayer(OpponentZero), &opponent_1);
     let opponent_2_memory = new_memory(OpponentPlayer(OpponentOne), &opponent_2);
     let opponent_3_memory = new_memory(OpponentPlayer(OpponentTwo), &opponent_3);
+    let player_memory = new_memory(TeammatePlayer(ThePlayer), &player);
 
-    //TODO let player decide at game start how first player will be determined
-    let current_player = Some(rng.gen::<Player>());
+    let current_player = Some(settings.first_player.unwrap_or_else(|| rng.gen::<Player>()));
+
+    let names = pick_names(&settings, &mut rng);
+    let personalities = pick_personalities(&mut rng);
 
     State {
         rng: rng,
@@ -93,22 +208,86 @@ fn make_state(size: Size, title_screen: bool, mut rng: StdRng) -> State {
         opponent_1_memory: opponent_1_memory,
         opponent_2_memory: opponent_2_memory,
         opponent_3_memory: opponent_3_memory,
+        player_memory,
         menu_state: Main,
         declaration: None,
         current_player: current_player,
-        ui_context: UIContext {
-            hot: 0,
-            active: 0,
-            next_hot: 0,
-        },
+        ui_context: UIContext::default(),
         card_offset: 0,
         suits_in_play_bits: 0xFF,
         player_points: 0,
         opponent_points: 0,
+        history: current_player.iter().map(|&p| TurnPassed(p)).collect(),
+        side_panel: DeclarationHistory,
+        log_scroll: 0,
+        notebook_open: false,
+        hand_order: AutoSorted,
+        selected_card: None,
+        settings,
+        names,
+        personalities,
+        dialogue: None,
+        settings_open: false,
+        settings_problems: Vec::new(),
+        autoplay_started: None,
+        autoplay_paused: false,
+        autopilot: false,
+        spectating: false,
+        reveal_hands: false,
+        notebook_seat: TeammatePlayer(ThePlayer),
+        debug_overlay: None,
+        notice: None,
+        replay: None,
+        replay_choices: None,
+        replay_choices_page: 0,
+        config_dir: None,
     }
 }
 
-fn shuffled_deck(rng: &mut StdRng) -> Deck {
+const NAME_POOL: [&str; 24] = ["Alice", "Bashir", "Chloe", "Dmitri", "Esther", "Farid", "Greta",
+                                "Hiroshi", "Ingrid", "Jamal", "Keiko", "Luis", "Mirela", "Nnamdi",
+                                "Olga", "Priya", "Quentin", "Rosa", "Sven", "Tamsin", "Umar",
+                                "Vera", "Wendell", "Yusuf"];
+
+//uses the names from the settings, filling in the blanks from NAME_POOL
+fn pick_names(settings: &Settings, rng: &mut GameRng) -> HashMap<Player, String> {
+    let mut pool: Vec<&str> = NAME_POOL
+        .iter()
+        .cloned()
+        .filter(|&name| !settings.names.values().any(|n| n == name))
+        .collect();
+    rng.shuffle(&mut pool);
+
+    Player::all_values()
+        .into_iter()
+        .map(|player| {
+            let name = match settings.names.get(&player) {
+                Some(name) if !name.is_empty() => name.clone(),
+                _ => pool.pop().map(|name| name.to_string()).unwrap_or_else(|| player.to_string()),
+            };
+
+            (player, name)
+        })
+        .collect()
+}
+
+fn pick_personalities(rng: &mut GameRng) -> HashMap<Player, Personality> {
+    let personalities = [Plain, Cheerful, Grumpy, Smug];
+
+    Player::all_values()
+        .into_iter()
+        .map(|player| {
+            let personality = match player {
+                TeammatePlayer(ThePlayer) => Plain,
+                _ => *rng.choose(&personalities).unwrap_or(&Plain),
+            };
+
+            (player, personality)
+        })
+        .collect()
+}
+
+fn shuffled_deck(rng: &mut GameRng) -> Deck {
     let mut deck = Card::all_values();
 
     rng.shuffle(&mut deck);
@@ -128,210 +307,1294 @@ pub fn hand_height(height: i32) -> i32 {
 #[no_mangle]
 //returns true if quit requested
 pub fn update_and_render(platform: &Platform, state: &mut State, events: &mut Vec<Event>) -> bool {
-    // if state.title_screen {
-    //
-    //     for event in events {
-    //         cross_mode_event_handling(platform, state, event);
-    //         match *event {
-    //             Event::Close |
-    //             Event::KeyPressed { key: KeyCode::Escape, ctrl: _, shift: _ } => return true,
-    //             Event::KeyPressed { key: _, ctrl: _, shift: _ } => state.title_screen = false,
-    //             _ => (),
-    //         }
-    //     }
-    //
-    //     draw(platform, state);
-    //
-    //     false
-    // } else {
-    game_update_and_render(platform, state, events)
-    // }
+    if state.title_screen {
+        title_update_and_render(platform, state, events)
+    } else {
+        game_update_and_render(platform, state, events)
+    }
 }
 
-pub fn game_update_and_render(platform: &Platform,
-                              state: &mut State,
-                              events: &mut Vec<Event>)
-                              -> bool {
-    let mut left_mouse_pressed = false;
-    let mut left_mouse_released = false;
+fn title_update_and_render(platform: &Platform,
+                           state: &mut State,
+                           events: &mut Vec<Event>)
+                           -> bool {
+    let mut input = UiInput::default();
 
     for event in events {
         cross_mode_event_handling(platform, state, event);
 
         match *event {
-            Event::KeyPressed {
-                key: KeyCode::MouseLeft,
-                ctrl: _,
-                shift: _,
-            } => {
-                left_mouse_pressed = true;
-            }
-            Event::KeyReleased {
-                key: KeyCode::MouseLeft,
-                ctrl: _,
-                shift: _,
-            } => {
-                left_mouse_released = true;
-            }
-            Event::Close |
+            Event::Close => return true,
             Event::KeyReleased {
                 key: KeyCode::Escape,
                 ctrl: _,
                 shift: _,
             } => {
-                match state.menu_state {
-                    Quit => return true,
-                    _ => state.menu_state = Quit,
+                if state.settings_open {
+                    close_settings(state);
+                } else if state.replay_choices.is_some() {
+                    state.replay_choices = None;
+                } else {
+                    return true;
                 }
             }
-
-            _ => (),
+            _ => handle_ui_event(event, &mut input),
         }
     }
 
+    state.ui_context.keyboard = input.keyboard;
+    state.ui_context.frame_init();
+
     let size = (platform.size)();
 
-    let outer = SpecRect {
-        x: MENU_OFFSET,
-        y: MENU_TOP_HEIGHT_OFFSET,
-        w: size.width - 2 * MENU_OFFSET,
-        h: size.height - (MENU_TOP_HEIGHT_OFFSET + MENU_BOTTOM_HEIGHT_OFFSET),
+    let inner = draw_full_screen_box(platform);
+
+    if state.settings_open {
+        draw_settings_menu(platform,
+                           state,
+                           &inner,
+                           input.left_mouse_pressed,
+                           input.left_mouse_released);
+
+        return false;
+    }
+
+    if state.replay_choices.is_some() {
+        draw_replay_choices(platform,
+                            state,
+                            &inner,
+                            input.left_mouse_pressed,
+                            input.left_mouse_released);
+
+        return false;
+    }
+
+    print_horizontally_centered_line(platform, &inner, "Canadian Fish", inner.y + 4);
+
+    if let Some(ref notice) = state.notice {
+        print_horizontally_centered_line(platform, &inner, notice, inner.y + 6);
+    }
+
+    let button_width = 20;
+    let mut y = inner.y + 9;
+
+    if save_file::has_saved_game(state.config_dir.as_deref()) {
+        let continue_button = ButtonSpec {
+            x: inner.x + (inner.w - button_width) / 2,
+            y,
+            w: button_width,
+            h: 3,
+            text: "Continue".to_string(),
+            id: 9004,
+        };
+
+        if do_button(platform,
+                     &mut state.ui_context,
+                     &continue_button,
+                     input.left_mouse_pressed,
+                     input.left_mouse_released) {
+            continue_saved_game(state, size);
+
+            return false;
+        }
+
+        y += 4;
+    }
+
+    let new_game_button = ButtonSpec {
+        x: inner.x + (inner.w - button_width) / 2,
+        y,
+        w: button_width,
+        h: 3,
+        text: "New Game".to_string(),
+        id: 9000,
     };
 
-    draw_double_line_rect(platform, outer.x, outer.y, outer.w, outer.h);
+    if do_button(platform,
+                 &mut state.ui_context,
+                 &new_game_button,
+                 input.left_mouse_pressed,
+                 input.left_mouse_released) {
+        let settings = state.settings.clone();
+        let config_dir = state.config_dir.take();
+        *state = new_game(size, settings);
+        state.config_dir = config_dir;
+
+        return false;
+    }
 
-    let inner = SpecRect {
-        x: outer.x + 1,
-        y: outer.y + 1,
-        w: outer.w - 2,
-        h: outer.h - 2,
+    y += 4;
+
+    let watch_button = ButtonSpec {
+        y,
+        text: "Watch a Game".to_string(),
+        id: 9003,
+        ..new_game_button
     };
 
-    state.ui_context.frame_init();
+    if do_button(platform,
+                 &mut state.ui_context,
+                 &watch_button,
+                 input.left_mouse_pressed,
+                 input.left_mouse_released) {
+        let settings = state.settings.clone();
+        let config_dir = state.config_dir.take();
+        *state = new_spectated_game(size, settings);
+        state.config_dir = config_dir;
+
+        return false;
+    }
 
-    let on_quit_screen = match state.menu_state {
-        Quit => true,
-        _ => false,
+    y += 4;
+
+    let settings_button = ButtonSpec {
+        y,
+        text: "Settings".to_string(),
+        id: 9001,
+        ..watch_button
     };
 
-    if on_quit_screen {
-        show_quit_screen(platform,
-                         state,
-                         left_mouse_pressed,
-                         left_mouse_released,
-                         &inner);
-    } else if state.suits_in_play_bits == 0 {
-        let mid_y = inner.y + (inner.h / 2);
-        print_horizontally_centered_line(platform,
-                                         &inner,
-                                         if state.player_points > state.opponent_points {
-                                             "Your team won"
-                                         } else if state.player_points < state.opponent_points {
-                                             "The other team won"
-                                         } else {
-                                             "It was a tie."
-                                         },
-                                         mid_y - 3);
+    if do_button(platform,
+                 &mut state.ui_context,
+                 &settings_button,
+                 input.left_mouse_pressed,
+                 input.left_mouse_released) {
+        state.settings_open = true;
+    }
+
+    y += 4;
+
+    let quit_button = ButtonSpec {
+        y,
+        text: "Quit".to_string(),
+        id: 9002,
+        ..settings_button
+    };
+
+    let quit = do_button(platform,
+                         &mut state.ui_context,
+                         &quit_button,
+                         input.left_mouse_pressed,
+                         input.left_mouse_released);
+
+    //a plain line off to the side, there's only room for five buttons and
+    //with Continue the Quit button reaches the bottom row
+    let replay_text = "Replay a saved game";
+    if do_text_button(platform,
+                      &mut state.ui_context,
+                      (inner.x + inner.w - replay_text.chars().count() as i32 - 2,
+                       inner.y + inner.h - 1),
+                      replay_text,
+                      9005,
+                      input.left_mouse_pressed,
+                      input.left_mouse_released) {
+        state.replay_choices = Some(replay_choices(state.config_dir.as_deref()));
+        state.replay_choices_page = 0;
+    }
+
+    quit
+}
+
+//how many records fit on the screen at once
+const REPLAY_CHOICE_PAGE_SIZE: usize = 4;
 
-        print_horizontally_centered_line(platform, &inner, "Final Score", mid_y - 1);
+//every saved record, described well enough to tell them apart
+fn replay_choices(config_dir: Option<&Path>) -> Vec<(String, String)> {
+    game_record::saved_records(config_dir)
+        .iter()
+        .map(|path| {
+            let name = path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let description = match game_record::load(path) {
+                Ok(ref record) if record.finished() => {
+                    let (us, them) = record.score();
+                    format!("{}  {}:{}", name, us, them)
+                }
+                Ok(_) => format!("{}  unfinished", name),
+                Err(_) => format!("{}  unreadable", name),
+            };
+
+            (path.to_string_lossy().into_owned(), description)
+        })
+        .collect()
+}
+
+fn draw_replay_choices(platform: &Platform,
+                       state: &mut State,
+                       inner: &SpecRect,
+                       left_mouse_pressed: bool,
+                       left_mouse_released: bool) {
+    let size = (platform.size)();
+
+    let choices = state.replay_choices.clone().unwrap_or_default();
+
+    let page_count = choices.len().div_ceil(REPLAY_CHOICE_PAGE_SIZE);
+    let page = state.replay_choices_page.min(page_count.saturating_sub(1));
+
+    let heading = if page_count > 1 {
+        format!("Replay a Game, page {} of {}", page + 1, page_count)
+    } else {
+        "Replay a Game".to_string()
+    };
+    print_horizontally_centered_line(platform, inner, &heading, inner.y + 4);
+
+    if let Some(ref notice) = state.notice {
+        print_horizontally_centered_line(platform, inner, notice, inner.y + 6);
+    }
+
+    if choices.is_empty() {
         print_horizontally_centered_line(platform,
-                                         &inner,
-                                         &format!("{}:{}",
-                                                 state.player_points,
-                                                 state.opponent_points),
-                                         mid_y);
-        print_horizontally_centered_line(platform, &inner, "  Us Them", mid_y + 1);
+                                         inner,
+                                         "No saved records yet, use Save record on the quit \
+                                          screen during a game",
+                                         inner.y + 9);
+    }
 
+    let page_choices = choices
+        .iter()
+        .skip(page * REPLAY_CHOICE_PAGE_SIZE)
+        .take(REPLAY_CHOICE_PAGE_SIZE);
 
-        let restart_button = ButtonSpec {
-            x: inner.x + ((inner.w - 14) / 2),
-            y: mid_y + 3,
-            w: 14,
+    let button_width = 40;
+    let mut y = inner.y + 8;
+
+    for (i, (path, description)) in page_choices.enumerate() {
+        let choice_button = ButtonSpec {
+            x: inner.x + (inner.w - button_width) / 2,
+            y,
+            w: button_width,
             h: 3,
-            text: "Restart".to_string(),
-            id: 1223,
+            text: description.clone(),
+            id: 9200 + i as UiId,
         };
 
         if do_button(platform,
                      &mut state.ui_context,
-                     &restart_button,
+                     &choice_button,
                      left_mouse_pressed,
                      left_mouse_released) {
-            *state = new_state(size);
-        }
-    } else if let Some(declaration) = state.declaration {
-        match declaration {
-            DeclareStep1 => {
-                draw_subsuit_menu(platform,
-                                  state,
-                                  inner,
-                                  left_mouse_pressed,
-                                  left_mouse_released,
-                                  &|state, subsuit| {
-                                      state.declaration = Some(DeclareStep2(subsuit,
-                                                                            [ThePlayer; 6]));
-                                  },
-                                  true)
-            }
-            DeclareStep2(subsuit, teammates) => {
-                draw_declare_radio_buttons(platform,
-                                           state,
-                                           inner,
-                                           left_mouse_pressed,
-                                           left_mouse_released,
-                                           subsuit,
-                                           teammates)
-            }
-            DeclareStep3(info) => {
-                draw_declare_result(platform,
-                                    state,
-                                    inner,
-                                    left_mouse_pressed,
-                                    left_mouse_released,
-                                    info)
+            let started = game_record::load(Path::new(path))
+                .and_then(|record| start_replay(state, size, record));
+
+            if let Err(problem) = started {
+                state.notice = Some(problem);
             }
 
+            return;
         }
-    } else if let Some(current_player) = state.current_player {
 
-        if player_hand(state, current_player).len() == 0 {
-            match current_player {
-                TeammatePlayer(ThePlayer) => {
-                    draw_teammate_selection(platform,
-                                            state,
-                                            inner,
-                                            left_mouse_pressed,
-                                            left_mouse_released)
-                }
-                TeammatePlayer(teammate) => {
-                    state.current_player =
-                        or_available_opponent(state,
-                                              get_available_teammate(state,
-                                                                     Some(teammate),
-                                                                     MostCards))
-                }
-                OpponentPlayer(opponent) => {
-                    state.current_player =
-                        or_available_teammate(state,
-                                              get_available_opponent(state,
-                                                                     Some(opponent),
-                                                                     MostCards))
-                }
-            }
+        y += 4;
+    }
 
-        } else {
-            match state.menu_state {
-                Quit => {
-                    show_quit_screen(platform,
-                                     state,
-                                     left_mouse_pressed,
-                                     left_mouse_released,
-                                     &inner);
+    let back_button = ButtonSpec {
+        x: inner.x + (inner.w - 20) / 2,
+        y: inner.y + inner.h - 4,
+        w: 20,
+        h: 3,
+        text: "Back".to_string(),
+        id: 9299,
+    };
+
+    if page > 0 {
+        let newer_button = ButtonSpec {
+            x: back_button.x - 16,
+            w: 14,
+            text: "← Newer".to_string(),
+            id: 9297,
+            ..back_button
+        };
+
+        if do_button(platform,
+                     &mut state.ui_context,
+                     &newer_button,
+                     left_mouse_pressed,
+                     left_mouse_released) {
+            state.replay_choices_page = page - 1;
+        }
+    }
+
+    if page + 1 < page_count {
+        let older_button = ButtonSpec {
+            x: back_button.x + back_button.w + 2,
+            w: 14,
+            text: "Older →".to_string(),
+            id: 9298,
+            ..back_button
+        };
+
+        if do_button(platform,
+                     &mut state.ui_context,
+                     &older_button,
+                     left_mouse_pressed,
+                     left_mouse_released) {
+            state.replay_choices_page = page + 1;
+        }
+    }
+
+    if do_button(platform,
+                 &mut state.ui_context,
+                 &back_button,
+                 left_mouse_pressed,
+                 left_mouse_released) {
+        state.replay_choices = None;
+        state.notice = None;
+    }
+}
+
+//checks the whole record can be played out, then shows it from the start
+fn start_replay(state: &mut State, size: Size, record: GameRecord) -> Result<(), String> {
+    replay_position(size, state.settings.clone(), &record, replay_length(&record))?;
+
+    let mut position = replay_position(size, state.settings.clone(), &record, 0)?;
+    position.settings_problems = state.settings_problems.clone();
+    position.config_dir = state.config_dir.take();
+    position.replay = Some(Replay { record, step: 0 });
+
+    *state = position;
+
+    Ok(())
+}
+
+//the number of asks and declarations, which is what the replay steps through
+fn replay_length(record: &GameRecord) -> usize {
+    record.moves.iter().filter(|entry| !matches!(**entry, TurnPassed(_))).count()
+}
+
+//moves the replay along, keeping how the table is being looked at
+fn go_to_replay_step(state: &mut State, size: Size, delta: i64) {
+    let replay = match state.replay.take() {
+        Some(replay) => replay,
+        None => return,
+    };
+
+    let last = replay_length(&replay.record) as i64;
+    let step = (replay.step as i64).saturating_add(delta).max(0).min(last) as usize;
+
+    match replay_position(size, state.settings.clone(), &replay.record, step) {
+        Ok(mut position) => {
+            position.settings_problems = state.settings_problems.clone();
+            position.config_dir = state.config_dir.take();
+            position.side_panel = state.side_panel;
+            position.notebook_open = state.notebook_open;
+            position.notebook_seat = state.notebook_seat;
+            position.reveal_hands = state.reveal_hands;
+            position.replay = Some(Replay { step, ..replay });
+
+            *state = position;
+        }
+        //start_replay already played the whole thing so this shouldn't happen
+        Err(_) => state.replay = Some(replay),
+    }
+}
+
+//a table set up from the record's deal with the first `step` asks and
+//declarations played out through the usual game code, so every seat's
+//memory is what it was at that point. Turns that passed afterwards are
+//included, so it shows who is about to play.
+fn replay_position(size: Size,
+                   settings: Settings,
+                   record: &GameRecord,
+                   step: usize)
+                   -> Result<State, String> {
+    let mut state = new_game(size, settings);
+    state.spectating = true;
+    state.autopilot = true;
+    state.settings.rules = record.rules;
+    state.history.clear();
+    state.current_player = None;
+
+    for &seat in ALL_SEATS.iter() {
+        let hand: Hand = record
+            .deal
+            .get(&seat)
+            .map(|hand| {
+                     hand.iter()
+                         .map(|card| {
+                                  Card {
+                                      suit: card.suit,
+                                      value: card.value,
+                                  }
+                              })
+                         .collect()
+                 })
+            .unwrap_or_default();
+
+        *get_memory_mut(&mut state, seat) = new_memory(seat, &hand);
+        *player_hand_mut(&mut state, seat) = hand;
+
+        if let Some(name) = record.names.get(&seat) {
+            state.names.insert(seat, name.clone());
+        }
+    }
+
+    let mut played = 0;
+    for (i, entry) in record.moves.iter().enumerate() {
+        if !matches!(*entry, TurnPassed(_)) {
+            if played == step {
+                break;
+            }
+            played += 1;
+        }
+
+        play_recorded_move(&mut state, entry)
+            .map_err(|problem| format!("move {}: {}", i + 1, problem))?;
+    }
+
+    state.dialogue = None;
+
+    Ok(state)
+}
+
+//makes a move from a record, checking it came out the way the record says
+fn play_recorded_move(state: &mut State, entry: &HistoryEntry) -> Result<(), String> {
+    match *entry {
+        TurnPassed(player) => set_current_player(state, Some(player)),
+        Asked(ask_vector, suit, value, recorded_has_card) => {
+            let (_, target) = ask_vector_players(ask_vector);
+
+            if has_card(player_hand(state, target), suit, value) != recorded_has_card {
+                return Err(format!("{} {} the {}{}",
+                                   target,
+                                   if recorded_has_card {
+                                       "doesn't have"
+                                   } else {
+                                       "has"
+                                   },
+                                   value,
+                                   suit));
+            }
+
+            resolve_ask(state, ask_vector, suit, value);
+        }
+        Declared(recorded) => {
+            let info = match recorded.declarer {
+                TeammatePlayer(declarer) => {
+                    let mut teammates = [ThePlayer; 6];
+                    for (i, &player) in recorded.claimed.iter().enumerate() {
+                        match player {
+                            TeammatePlayer(teammate) => teammates[i] = teammate,
+                            OpponentPlayer(_) => {
+                                return Err("a card was given to the other team".to_string())
+                            }
+                        }
+                    }
+
+                    TeammateDInfo(declarer, recorded.subsuit, teammates)
+                }
+                OpponentPlayer(declarer) => {
+                    let mut opponents = [OpponentZero; 6];
+                    for (i, &player) in recorded.claimed.iter().enumerate() {
+                        match player {
+                            OpponentPlayer(opponent) => opponents[i] = opponent,
+                            TeammatePlayer(_) => {
+                                return Err("a card was given to the other team".to_string())
+                            }
+                        }
+                    }
+
+                    OpponentDInfo(declarer, recorded.subsuit, opponents)
+                }
+            };
+
+            resolve_declaration(state, info);
+
+            match state.history.last() {
+                Some(&Declared(result)) if result.actual == recorded.actual &&
+                                           result.correct == recorded.correct &&
+                                           result.cancelled == recorded.cancelled => {}
+                _ => {
+                    return Err(format!("the cards of {} weren't where the record says",
+                                       recorded.subsuit))
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+//the game so far, with the deal found by handing back every card that has
+//moved since
+fn game_record(state: &State) -> GameRecord {
+    let mut deal: HashMap<Player, Hand> = ALL_SEATS
+        .iter()
+        .map(|&seat| {
+                 (seat,
+                  player_hand(state, seat)
+                      .iter()
+                      .map(|card| {
+                               Card {
+                                   suit: card.suit,
+                                   value: card.value,
+                               }
+                           })
+                      .collect())
+             })
+        .collect();
+
+    for entry in state.history.iter().rev() {
+        match *entry {
+            Asked(ask_vector, suit, value, true) => {
+                let (source, target) = ask_vector_players(ask_vector);
+
+                if let Some(card) = deal.get_mut(&source)
+                       .and_then(|hand| remove_from_hand(hand, suit, value)) {
+                    deal.entry(target).or_default().push(card);
+                }
+            }
+            Declared(record) => {
+                for (i, &(suit, value)) in pairs_from_subsuit(record.subsuit).iter().enumerate() {
+                    deal.entry(record.actual[i]).or_default().push(Card { suit, value });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for hand in deal.values_mut() {
+        hand.sort();
+    }
+
+    GameRecord {
+        seed: Some(state.rng.seed()),
+        rules: state.settings.rules,
+        names: state.names.clone(),
+        deal,
+        moves: state.history.clone(),
+    }
+}
+
+//called when the player leaves a game so they can pick it up again with
+//Continue, or so a finished one isn't offered
+fn autosave(state: &mut State) {
+    //a replay isn't a game of theirs
+    if state.replay.is_some() {
+        return;
+    }
+
+    //they'll come back to the table, not the quit screen
+    state.menu_state = Main;
+
+    let result = if state.suits_in_play_bits == 0 {
+        save_file::delete_saved_game(state.config_dir.as_deref())
+    } else {
+        save_file::save_game(state.config_dir.as_deref(), state)
+    };
+
+    //shown on the title screen, stdout may be where the game is being drawn
+    if let Err(problem) = result {
+        state.notice = Some(problem);
+    }
+}
+
+//the settings stay as they are now, since they may have been changed since
+//the game was saved
+fn continue_saved_game(state: &mut State, size: Size) {
+    let settings = state.settings.clone();
+
+    match save_file::load_game(state.config_dir.as_deref(), new_game(size, settings.clone())) {
+        Ok(mut saved) => {
+            saved.settings = settings;
+            saved.settings_problems = state.settings_problems.clone();
+            saved.config_dir = state.config_dir.take();
+            saved.title_screen = false;
+            saved.notice = None;
+
+            *state = saved;
+        }
+        Err(problem) => state.notice = Some(problem),
+    }
+}
+
+//draws a box around nearly the whole screen and returns the space inside it
+fn draw_full_screen_box(platform: &Platform) -> SpecRect {
+    let size = (platform.size)();
+
+    let outer = SpecRect {
+        x: MENU_OFFSET,
+        y: MENU_TOP_HEIGHT_OFFSET,
+        w: size.width - 2 * MENU_OFFSET,
+        h: size.height - 2 * MENU_TOP_HEIGHT_OFFSET,
+    };
+
+    draw_double_line_rect(platform, outer.x, outer.y, outer.w, outer.h);
+
+    SpecRect {
+        x: outer.x + 1,
+        y: outer.y + 1,
+        w: outer.w - 2,
+        h: outer.h - 2,
+    }
+}
+
+fn draw_settings_menu(platform: &Platform,
+                      state: &mut State,
+                      rect: &SpecRect,
+                      left_mouse_pressed: bool,
+                      left_mouse_released: bool) {
+    let x = rect.x + 4;
+    let mut y = rect.y + 1;
+
+    (platform.print_xy)(x, y, "Settings");
+    y += 2;
+
+    let difficulty_text = format!("Difficulty: {}",
+                                  match state.settings.difficulty {
+                                      Easy => "easy",
+                                      Normal => "normal",
+                                      Hard => "hard",
+                                  });
+    if do_text_button(platform,
+                      &mut state.ui_context,
+                      (x, y),
+                      &difficulty_text,
+                      9100,
+                      left_mouse_pressed,
+                      left_mouse_released) {
+        state.settings.difficulty = match state.settings.difficulty {
+            Easy => Normal,
+            Normal => Hard,
+            Hard => Easy,
+        };
+    }
+    y += 1;
+
+    let rules_text = format!("Rules: {}",
+                             match state.settings.rules {
+                                 Standard => "standard",
+                                 MisassignedCancelled => {
+                                     "a half-suit misassigned within a team is cancelled"
+                                 }
+                             });
+    if do_text_button(platform,
+                      &mut state.ui_context,
+                      (x, y),
+                      &rules_text,
+                      9101,
+                      left_mouse_pressed,
+                      left_mouse_released) {
+        state.settings.rules = match state.settings.rules {
+            Standard => MisassignedCancelled,
+            MisassignedCancelled => Standard,
+        };
+    }
+    y += 1;
+
+    let first_player_text = format!("First player: {}",
+                                    match state.settings.first_player {
+                                        //the seat, since its name may be random
+                                        Some(player) => player.to_string(),
+                                        None => "random".to_string(),
+                                    });
+    if do_text_button(platform,
+                      &mut state.ui_context,
+                      (x, y),
+                      &first_player_text,
+                      9102,
+                      left_mouse_pressed,
+                      left_mouse_released) {
+        state.settings.first_player = match state.settings.first_player {
+            None => ALL_SEATS.first().cloned(),
+            Some(player) => {
+                ALL_SEATS
+                    .iter()
+                    .skip_while(|&&p| p != player)
+                    .nth(1)
+                    .cloned()
+            }
+        };
+    }
+    y += 1;
+
+    let autoplay_text = format!("CPU asks: {}",
+                                match state.settings.autoplay {
+                                    AutoplaySpeed::Instant => "instant",
+                                    AutoplaySpeed::Fast => "fast",
+                                    AutoplaySpeed::Normal => "normal",
+                                    AutoplaySpeed::StepByStep => "step by step",
+                                });
+    if do_text_button(platform,
+                      &mut state.ui_context,
+                      (x, y),
+                      &autoplay_text,
+                      9105,
+                      left_mouse_pressed,
+                      left_mouse_released) {
+        state.settings.autoplay = match state.settings.autoplay {
+            AutoplaySpeed::StepByStep => AutoplaySpeed::Normal,
+            AutoplaySpeed::Normal => AutoplaySpeed::Fast,
+            AutoplaySpeed::Fast => AutoplaySpeed::Instant,
+            AutoplaySpeed::Instant => AutoplaySpeed::StepByStep,
+        };
+    }
+    y += 1;
+
+    let acknowledge_text = format!("Wait for a click on your team's asks and asks to you: {}",
+                                   if state.settings.acknowledge_team_asks {
+                                       "yes"
+                                   } else {
+                                       "no"
+                                   });
+    if do_text_button(platform,
+                      &mut state.ui_context,
+                      (x, y),
+                      &acknowledge_text,
+                      9106,
+                      left_mouse_pressed,
+                      left_mouse_released) {
+        state.settings.acknowledge_team_asks = !state.settings.acknowledge_team_asks;
+    }
+    y += 1;
+
+    (platform.print_xy)(x, y, "Seed");
+    let mut seed_text = state.settings.seed.map(|seed| seed.to_string()).unwrap_or_default();
+    if do_settings_field(platform,
+                         state,
+                         (x, y),
+                         &mut seed_text,
+                         9103,
+                         left_mouse_pressed,
+                         left_mouse_released) {
+        if seed_text.is_empty() {
+            state.settings.seed = None;
+        } else if let Ok(seed) = seed_text.parse() {
+            state.settings.seed = Some(seed);
+        }
+    }
+    (platform.print_xy)(x + SETTINGS_LABEL_WIDTH + SETTINGS_FIELD_WIDTH + 2,
+                        y,
+                        "blank for a new deal every game");
+    y += 2;
+
+    (platform.print_xy)(x, y, "Names");
+    (platform.print_xy)(x + SETTINGS_LABEL_WIDTH + SETTINGS_FIELD_WIDTH + 2,
+                        y + 1,
+                        "blank for a random name");
+    y += 1;
+
+    for (i, &seat) in ALL_SEATS.iter().enumerate() {
+        (platform.print_xy)(x + 2, y, &seat.to_string());
+
+        let mut name = state.settings.names.get(&seat).cloned().unwrap_or_default();
+        if do_settings_field(platform,
+                             state,
+                             (x, y),
+                             &mut name,
+                             9110 + i as UiId,
+                             left_mouse_pressed,
+                             left_mouse_released) {
+            state.settings.names.insert(seat, name);
+        }
+        y += 1;
+    }
+    y += 1;
+
+    (platform.print_xy)(x, y, "Display, takes effect the next time the game starts");
+    y += 1;
+
+    (platform.print_xy)(x + 2, y, "Window size");
+    let mut window_size_text = format!("{}x{}",
+                                       state.settings.display.window_width,
+                                       state.settings.display.window_height);
+    if do_settings_field(platform,
+                         state,
+                         (x, y),
+                         &mut window_size_text,
+                         9120,
+                         left_mouse_pressed,
+                         left_mouse_released) {
+        if let Ok((width, height)) = settings_file::parse_dimensions(&window_size_text) {
+            state.settings.display.window_width = width;
+            state.settings.display.window_height = height;
+        }
+    }
+    y += 1;
+
+    (platform.print_xy)(x + 2, y, "Font");
+    let mut font = state.settings.display.font.clone();
+    if do_settings_field(platform,
+                         state,
+                         (x, y),
+                         &mut font,
+                         9121,
+                         left_mouse_pressed,
+                         left_mouse_released) {
+        state.settings.display.font = font;
+    }
+    y += 1;
+
+    (platform.print_xy)(x + 2, y, "Font size");
+    let mut font_size_text = format!("{}x{}",
+                                     state.settings.display.font_width,
+                                     state.settings.display.font_height);
+    if do_settings_field(platform,
+                         state,
+                         (x, y),
+                         &mut font_size_text,
+                         9122,
+                         left_mouse_pressed,
+                         left_mouse_released) {
+        if let Ok((width, height)) = settings_file::parse_dimensions(&font_size_text) {
+            state.settings.display.font_width = width;
+            state.settings.display.font_height = height;
+        }
+    }
+    y += 2;
+
+    for problem in state.settings_problems.iter().take(2) {
+        let foreground = (platform.get_foreground)();
+        (platform.set_foreground)(WRONG_COLOR);
+        (platform.print_xy)(x, y, &truncate(problem, (rect.w - 8) as usize));
+        (platform.set_foreground)(foreground);
+
+        y += 1;
+    }
+    if state.settings_problems.len() > 2 {
+        (platform.print_xy)(x,
+                            y,
+                            &format!("and {} more problems with the settings file",
+                                    state.settings_problems.len() - 2));
+    }
+
+    let back_button = ButtonSpec {
+        x: rect.x + (rect.w - 20) / 2,
+        y: rect.y + rect.h - 4,
+        w: 20,
+        h: 3,
+        text: "Back".to_string(),
+        id: 9104,
+    };
+
+    if do_button(platform,
+                 &mut state.ui_context,
+                 &back_button,
+                 left_mouse_pressed,
+                 left_mouse_released) {
+        close_settings(state);
+    }
+}
+
+fn do_settings_field(platform: &Platform,
+                     state: &mut State,
+                     (x, y): (i32, i32),
+                     text: &mut String,
+                     id: UiId,
+                     left_mouse_pressed: bool,
+                     left_mouse_released: bool)
+                     -> bool {
+    do_text_field(platform,
+                  &mut state.ui_context,
+                  &SpecRect {
+                       x: x + SETTINGS_LABEL_WIDTH,
+                       y,
+                       w: SETTINGS_FIELD_WIDTH,
+                       h: 1,
+                   },
+                  text,
+                  id,
+                  left_mouse_pressed,
+                  left_mouse_released)
+}
+
+//leaving the settings screen saves them
+fn close_settings(state: &mut State) {
+    state.settings_open = false;
+
+    state.settings_problems = match settings_file::save(state.config_dir.as_deref(), &state.settings) {
+        Ok(()) => Vec::new(),
+        Err(problem) => vec![problem],
+    };
+}
+
+const SETTINGS_FIELD_WIDTH: i32 = 24;
+const SETTINGS_LABEL_WIDTH: i32 = 16;
+
+pub fn game_update_and_render(platform: &Platform,
+                              state: &mut State,
+                              events: &mut Vec<Event>)
+                              -> bool {
+    let mut input = UiInput::default();
+    let mut scroll_delta = 0;
+    let mut replay_delta: i64 = 0;
+
+    for event in events {
+        cross_mode_event_handling(platform, state, event);
+
+        match *event {
+            Event::KeyPressed {
+                key: KeyCode::Right,
+                ctrl: _,
+                shift: true,
+            } => {
+                input.keyboard.card_shift = 1;
+            }
+            Event::KeyPressed {
+                key: KeyCode::Right,
+                ctrl: _,
+                shift: false,
+            } if state.replay.is_some() && !state.settings_open => {
+                replay_delta = replay_delta.saturating_add(1);
+            }
+            Event::KeyPressed {
+                key: KeyCode::Left,
+                ctrl: _,
+                shift: false,
+            } if state.replay.is_some() && !state.settings_open => {
+                replay_delta = replay_delta.saturating_sub(1);
+            }
+            Event::KeyPressed {
+                key: KeyCode::Home,
+                ctrl: _,
+                shift: _,
+            } if state.replay.is_some() && !state.settings_open => {
+                replay_delta = i64::MIN;
+            }
+            Event::KeyPressed {
+                key: KeyCode::End,
+                ctrl: _,
+                shift: _,
+            } if state.replay.is_some() && !state.settings_open => {
+                replay_delta = i64::MAX;
+            }
+            Event::KeyPressed {
+                key: KeyCode::Left,
+                ctrl: _,
+                shift: true,
+            } => {
+                input.keyboard.card_shift = -1;
+            }
+            //the settings screen needs these keys for typing
+            Event::KeyPressed {
+                key: KeyCode::N,
+                ctrl: false,
+                shift: _,
+            } if !state.settings_open => {
+                state.notebook_open = !state.notebook_open;
+            }
+            Event::KeyPressed {
+                key: KeyCode::P,
+                ctrl: false,
+                shift: _,
+            } if !state.settings_open => {
+                state.autoplay_paused = !state.autoplay_paused;
+            }
+            Event::KeyPressed {
+                key: KeyCode::C,
+                ctrl: false,
+                shift: _,
+            } if !state.settings_open => {
+                toggle_autopilot(state);
+            }
+            Event::KeyPressed {
+                key: KeyCode::H,
+                ctrl: false,
+                shift: _,
+            } if state.spectating && !state.settings_open => {
+                state.reveal_hands = !state.reveal_hands;
+            }
+            Event::KeyPressed {
+                key: KeyCode::M,
+                ctrl: false,
+                shift: _,
+            } if state.spectating && !state.settings_open => {
+                next_notebook_seat(state);
+            }
+            #[cfg(feature = "debug")]
+            Event::KeyPressed {
+                key: KeyCode::F1,
+                ctrl: false,
+                shift: _,
+            } => {
+                state.debug_overlay = next_debug_memory_overlay(state.debug_overlay);
+            }
+            #[cfg(feature = "debug")]
+            Event::KeyPressed {
+                key: KeyCode::F2,
+                ctrl: false,
+                shift: _,
+            } => {
+                state.debug_overlay = match state.debug_overlay {
+                    Some(DebugOverlay::TrueHands) => None,
+                    _ => Some(DebugOverlay::TrueHands),
+                };
+            }
+            Event::MouseScroll { delta } => {
+                scroll_delta += delta;
+            }
+            Event::KeyPressed {
+                key: KeyCode::PageUp,
+                ctrl: _,
+                shift: _,
+            } => {
+                scroll_delta -= LOG_PAGE_SIZE;
+            }
+            Event::KeyPressed {
+                key: KeyCode::PageDown,
+                ctrl: _,
+                shift: _,
+            } => {
+                scroll_delta += LOG_PAGE_SIZE;
+            }
+            Event::KeyPressed {
+                key,
+                ctrl: false,
+                shift: _,
+            } if number_from_key(key).is_some() && !state.settings_open => {
+                input.keyboard.number = number_from_key(key);
+            }
+            Event::Close |
+            Event::KeyReleased {
+                key: KeyCode::Escape,
+                ctrl: _,
+                shift: _,
+            } => {
+                if state.settings_open {
+                    close_settings(state);
+                } else {
+                    match state.menu_state {
+                        Quit => {
+                            autosave(state);
+                            return true;
+                        }
+                        _ => state.menu_state = Quit,
+                    }
+                }
+            }
+
+            _ => handle_ui_event(event, &mut input),
+        }
+    }
+
+    let left_mouse_pressed = input.left_mouse_pressed;
+    let left_mouse_released = input.left_mouse_released;
+
+    if replay_delta != 0 {
+        go_to_replay_step(state, (platform.size)(), replay_delta);
+    }
+
+    #[cfg(feature = "debug")]
+    {
+        if let Some(overlay) = state.debug_overlay {
+            state.ui_context.keyboard = input.keyboard;
+            state.ui_context.frame_init();
+
+            let inner = draw_full_screen_box(platform);
+            draw_debug_overlay(platform, state, &inner, overlay);
+
+            return false;
+        }
+    }
+
+    if state.settings_open {
+        state.ui_context.keyboard = input.keyboard;
+        state.ui_context.frame_init();
+
+        let inner = draw_full_screen_box(platform);
+        draw_settings_menu(platform, state, &inner, left_mouse_pressed, left_mouse_released);
+
+        return false;
+    }
+
+    let size = (platform.size)();
+
+    //a replay keeps the log up even once every half-suit is declared
+    let game_over = state.suits_in_play_bits == 0 && state.replay.is_none();
+    let show_notebook = state.notebook_open && !game_over;
+
+    let table_growth = table_row_height(state) - TABLE_ROW_HEIGHT;
+
+    let side_panel = SpecRect {
+        x: size.width - (MENU_OFFSET + SIDE_PANEL_WIDTH),
+        y: MENU_TOP_HEIGHT_OFFSET + table_growth,
+        w: SIDE_PANEL_WIDTH,
+        h: size.height - (MENU_TOP_HEIGHT_OFFSET + MENU_BOTTOM_HEIGHT_OFFSET) - 2 * table_growth,
+    };
+
+    let outer = SpecRect {
+        x: MENU_OFFSET,
+        y: MENU_TOP_HEIGHT_OFFSET + table_growth,
+        w: if game_over || show_notebook {
+            size.width - 2 * MENU_OFFSET
+        } else {
+            side_panel.x - 2 * MENU_OFFSET
+        },
+        h: size.height - (MENU_TOP_HEIGHT_OFFSET + MENU_BOTTOM_HEIGHT_OFFSET) - 2 * table_growth,
+    };
+
+    draw_double_line_rect(platform, outer.x, outer.y, outer.w, outer.h);
+
+    let inner = SpecRect {
+        x: outer.x + 1,
+        y: outer.y + 1,
+        w: outer.w - 2,
+        h: outer.h - 2,
+    };
+
+    state.ui_context.keyboard = input.keyboard;
+    state.ui_context.frame_init();
+
+    let on_quit_screen = match state.menu_state {
+        Quit => true,
+        _ => false,
+    };
+
+    //the game carries on under the notebook, so a seat's memory can be
+    //watched as it changes, but nothing under it can be clicked
+    let (left_mouse_pressed, left_mouse_released) = if show_notebook {
+        (false, false)
+    } else {
+        (left_mouse_pressed, left_mouse_released)
+    };
+    if show_notebook {
+        state.ui_context.keyboard = KeyboardInput::default();
+    }
+    let notebook_rect = SpecRect {
+        x: inner.x,
+        y: inner.y,
+        w: inner.w,
+        h: inner.h,
+    };
+
+    if on_quit_screen {
+        show_quit_screen(platform,
+                         state,
+                         left_mouse_pressed,
+                         left_mouse_released,
+                         &inner);
+    } else if state.replay.is_some() {
+        draw_replay_controls(platform,
+                             state,
+                             &inner,
+                             left_mouse_pressed,
+                             left_mouse_released);
+    } else if game_over {
+        print_horizontally_centered_line(platform,
+                                         &inner,
+                                         if state.player_points > state.opponent_points {
+                                             "Your team won"
+                                         } else if state.player_points < state.opponent_points {
+                                             "The other team won"
+                                         } else {
+                                             "It was a tie."
+                                         },
+                                         inner.y);
+
+        print_horizontally_centered_line(platform, &inner, "Final Score", inner.y + 1);
+        print_horizontally_centered_line(platform,
+                                         &inner,
+                                         &format!("{}:{}",
+                                                 state.player_points,
+                                                 state.opponent_points),
+                                         inner.y + 2);
+        print_horizontally_centered_line(platform, &inner, "  Us Them", inner.y + 3);
+
+        for (i, record) in declaration_records(state).iter().enumerate() {
+            print_horizontally_centered_line(platform,
+                                             &inner,
+                                             &format!("{:<12}{:<10}declared by {} ({})",
+                                                     record.subsuit.to_string(),
+                                                     score_text(record),
+                                                     player_name(state, record.declarer),
+                                                     if record.correct {
+                                                         "correct"
+                                                     } else {
+                                                         "wrong"
+                                                     }),
+                                             inner.y + 5 + i as i32);
+        }
+
+        let restart_button = ButtonSpec {
+            x: inner.x + ((inner.w - 14) / 2),
+            y: inner.y + inner.h - 3,
+            w: 14,
+            h: 3,
+            text: "Restart".to_string(),
+            id: 1223,
+        };
+
+        if do_button(platform,
+                     &mut state.ui_context,
+                     &restart_button,
+                     left_mouse_pressed,
+                     left_mouse_released) {
+            restart(state, size);
+        }
+    } else if let Some(declaration) = state.declaration {
+        match declaration {
+            DeclareStep1 => {
+                draw_subsuit_menu(platform,
+                                  state,
+                                  inner,
+                                  left_mouse_pressed,
+                                  left_mouse_released,
+                                  &|state, subsuit| {
+                                      state.declaration = Some(DeclareStep2(subsuit,
+                                                                            [ThePlayer; 6]));
+                                  },
+                                  true)
+            }
+            DeclareStep2(subsuit, teammates) => {
+                draw_declare_radio_buttons(platform,
+                                           state,
+                                           inner,
+                                           left_mouse_pressed,
+                                           left_mouse_released,
+                                           subsuit,
+                                           teammates)
+            }
+            DeclareStep3(info) => {
+                draw_declare_result(platform,
+                                    state,
+                                    inner,
+                                    left_mouse_pressed,
+                                    left_mouse_released,
+                                    info)
+            }
+
+        }
+    } else if let Some(current_player) = state.current_player {
+
+        if player_hand(state, current_player).len() == 0 {
+            match current_player {
+                TeammatePlayer(ThePlayer) if !state.autopilot => {
+                    draw_teammate_selection(platform,
+                                            state,
+                                            inner,
+                                            left_mouse_pressed,
+                                            left_mouse_released)
+                }
+                TeammatePlayer(teammate) => {
+                    let next_player =
+                        or_available_opponent(state,
+                                              get_available_teammate(state,
+                                                                     Some(teammate),
+                                                                     MostCards));
+                    set_current_player(state, next_player);
+                }
+                OpponentPlayer(opponent) => {
+                    let next_player =
+                        or_available_teammate(state,
+                                              get_available_opponent(state,
+                                                                     Some(opponent),
+                                                                     MostCards));
+                    set_current_player(state, next_player);
+                }
+            }
+
+        } else {
+            match state.menu_state {
+                Quit => {
+                    show_quit_screen(platform,
+                                     state,
+                                     left_mouse_pressed,
+                                     left_mouse_released,
+                                     &inner);
 
                 }
                 Main => {
                     match current_player {
-                        TeammatePlayer(ThePlayer) => {
+                        TeammatePlayer(ThePlayer) if !state.autopilot => {
                             draw_main_menu(platform,
                                            state,
                                            inner,
@@ -341,26 +1604,37 @@ pub fn game_update_and_render(platform: &Platform,
                         _ => {
                             if let Some((ask_vector, suit, value)) =
                                 get_ask_info(state, current_player) {
-                                state.menu_state = AskStep4(ask_vector, suit, value);
+                                start_ask(state, ask_vector, suit, value);
                             } else {
                                 state.menu_state = Main;
                                 //TODO give player chance to declare first
-                                state.declaration = Some(guess_declaration(state));
+                                let declaration = guess_declaration(state);
+                                start_declaration(state, declaration);
                             }
                         }
                     }
                 }
                 AskStep1 => {
+                    draw_ask_breadcrumbs(platform,
+                                         state,
+                                         &inner,
+                                         left_mouse_pressed,
+                                         left_mouse_released);
                     draw_ask_opponent_menu(platform,
                                            state,
-                                           inner,
+                                           below_breadcrumbs(&inner),
                                            left_mouse_pressed,
                                            left_mouse_released)
                 }
                 AskStep2(opponent) => {
+                    draw_ask_breadcrumbs(platform,
+                                         state,
+                                         &inner,
+                                         left_mouse_pressed,
+                                         left_mouse_released);
                     draw_subsuit_menu(platform,
                                       state,
-                                      inner,
+                                      below_breadcrumbs(&inner),
                                       left_mouse_pressed,
                                       left_mouse_released,
                                       &|state, subsuit| {
@@ -369,9 +1643,14 @@ pub fn game_update_and_render(platform: &Platform,
                                       false)
                 }
                 AskStep3(opponent, subsuit) => {
+                    draw_ask_breadcrumbs(platform,
+                                         state,
+                                         &inner,
+                                         left_mouse_pressed,
+                                         left_mouse_released);
                     draw_ask_suit_menu(platform,
                                        state,
-                                       inner,
+                                       below_breadcrumbs(&inner),
                                        left_mouse_pressed,
                                        left_mouse_released,
                                        opponent,
@@ -394,6 +1673,19 @@ pub fn game_update_and_render(platform: &Platform,
         //TODO handle game end
     }
 
+    let left_mouse_pressed = input.left_mouse_pressed;
+    let left_mouse_released = input.left_mouse_released;
+
+    if show_notebook {
+        state.ui_context.keyboard = input.keyboard;
+
+        (platform.clear)(Some(Rect::from_values(notebook_rect.x,
+                                                notebook_rect.y,
+                                                notebook_rect.w,
+                                                notebook_rect.h)));
+        draw_notebook(platform, state, &notebook_rect);
+    }
+
     let screen_rect = SpecRect {
         x: 0,
         y: 0,
@@ -404,22 +1696,65 @@ pub fn game_update_and_render(platform: &Platform,
     if let Some(player) = state.current_player {
         let turn_string = match player {
             TeammatePlayer(ThePlayer) => "Your turn".to_string(),
-            _ => player.to_string() + "'s turn",
+            _ => player_name(state, player) + "'s turn",
         };
 
         print_horizontally_centered_line(platform, &screen_rect, &turn_string, 0);
     };
 
-    draw(platform, state);
+    if state.spectating {
+        draw_spectator_options(platform, state, left_mouse_pressed, left_mouse_released);
+    } else {
+        let autopilot_text = format!("CPU plays for you: {} (C)",
+                                     if state.autopilot { "on" } else { "off" });
+        if do_text_button(platform,
+                          &mut state.ui_context,
+                          (MENU_OFFSET, 0),
+                          &autopilot_text,
+                          2336,
+                          left_mouse_pressed,
+                          left_mouse_released) {
+            toggle_autopilot(state);
+        }
+    }
+
+    draw(platform, state);
+
+    draw_table(platform, state, &outer);
+
+    if !(game_over || show_notebook) {
+        draw_side_panel(platform,
+                        state,
+                        &side_panel,
+                        left_mouse_pressed,
+                        left_mouse_released,
+                        scroll_delta);
+    }
+
+    do_hand(platform, state, left_mouse_pressed, left_mouse_released);
+
+    //when a card is selected the window arrows move it instead of the window
+    let selected_index = state.selected_card.and_then(|(suit, value)| {
+        state.player.iter().position(|c| c.suit == suit && c.value == value)
+    });
+
+    let show_left_arrow = match selected_index {
+        Some(index) => index > 0,
+        None => state.card_offset > 0,
+    };
 
-    if state.card_offset > 0 {
+    if show_left_arrow {
         let hand_window_left = ButtonSpec {
             x: 1,
             y: size.height - 5,
             w: HAND_ARROW_WIDTH,
             h: HAND_ARROW_HEIGHT,
-            text: "←".to_string(),
-            id: 1223,
+            text: if selected_index.is_some() {
+                "⇐".to_string()
+            } else {
+                "←".to_string()
+            },
+            id: 1224,
         };
 
         if do_button(platform,
@@ -427,18 +1762,31 @@ pub fn game_update_and_render(platform: &Platform,
                      &hand_window_left,
                      left_mouse_pressed,
                      left_mouse_released) {
-            state.card_offset = state.card_offset.saturating_sub(1);
+            if selected_index.is_some() {
+                shift_selected_card(state, -1);
+            } else {
+                state.card_offset = state.card_offset.saturating_sub(1);
+            }
         }
 
     }
 
-    if state.player.len() - state.card_offset > HAND_WINDOW_SIZE {
+    let show_right_arrow = match selected_index {
+        Some(index) => index + 1 < state.player.len(),
+        None => state.player.len().saturating_sub(state.card_offset) > HAND_WINDOW_SIZE,
+    };
+
+    if show_right_arrow {
         let hand_window_right = ButtonSpec {
             x: size.width - (DECLARE_BUTTON_WIDTH + MENU_OFFSET + HAND_ARROW_WIDTH),
             y: size.height - (MENU_OFFSET + HAND_ARROW_HEIGHT),
             w: HAND_ARROW_WIDTH,
             h: HAND_ARROW_HEIGHT,
-            text: "→".to_string(),
+            text: if selected_index.is_some() {
+                "⇒".to_string()
+            } else {
+                "→".to_string()
+            },
             id: 2334,
         };
 
@@ -447,15 +1795,44 @@ pub fn game_update_and_render(platform: &Platform,
                      &hand_window_right,
                      left_mouse_pressed,
                      left_mouse_released) {
-            if state.player.get(state.card_offset + 1).is_some() {
+            if selected_index.is_some() {
+                shift_selected_card(state, 1);
+            } else if state.player.get(state.card_offset + 1).is_some() {
                 state.card_offset += 1;
             }
         }
     }
 
+    if state.ui_context.keyboard.card_shift != 0 {
+        let card_shift = state.ui_context.keyboard.card_shift;
+        shift_selected_card(state, card_shift);
+    }
+
+    let order_text = format!("Order: {}",
+                             match state.hand_order {
+                                 AutoSorted => "sorted",
+                                 GroupedByHalfSuit => "half-suits",
+                                 Manual => "manual",
+                             });
+    if do_text_button(platform,
+                      &mut state.ui_context,
+                      (size.width - (MENU_OFFSET + order_text.chars().count() as i32),
+                       size.height - 1),
+                      &order_text,
+                      2335,
+                      left_mouse_pressed,
+                      left_mouse_released) {
+        state.hand_order = match state.hand_order {
+            AutoSorted => GroupedByHalfSuit,
+            GroupedByHalfSuit => Manual,
+            Manual => AutoSorted,
+        };
+        sort_player_hand(state);
+    }
+
     let show_declare_button = match state.declaration {
         Some(DeclareStep3(_)) => false,
-        _ => teammate_hand(state, ThePlayer).len() > 0,
+        _ => !state.spectating && teammate_hand(state, ThePlayer).len() > 0,
     };
 
     if show_declare_button {
@@ -476,12 +1853,486 @@ pub fn game_update_and_render(platform: &Platform,
                      left_mouse_pressed,
                      left_mouse_released) {
             state.declaration = Some(DeclareStep1);
+            state.notebook_open = false;
+        }
+    }
+
+    if !game_over {
+        let notebook_button = ButtonSpec {
+            x: size.width -
+               (DECLARE_BUTTON_WIDTH + MENU_OFFSET + HAND_ARROW_WIDTH + MENU_OFFSET +
+                NOTEBOOK_BUTTON_WIDTH),
+            y: outer.y + outer.h + MENU_OFFSET,
+            w: NOTEBOOK_BUTTON_WIDTH,
+            h: 5,
+            text: if state.notebook_open {
+                "Back".to_string()
+            } else {
+                "Notebook".to_string()
+            },
+            id: 3446,
+        };
+
+        if do_button(platform,
+                     &mut state.ui_context,
+                     &notebook_button,
+                     left_mouse_pressed,
+                     left_mouse_released) {
+            state.notebook_open = !state.notebook_open;
         }
     }
 
     false
 }
 
+fn draw_side_panel(platform: &Platform,
+                   state: &mut State,
+                   rect: &SpecRect,
+                   left_mouse_pressed: bool,
+                   left_mouse_released: bool,
+                   scroll_delta: i32) {
+    let tab_width = rect.w / 2;
+
+    let tabs = [(DeclarationHistory, "Half-suits", 6778), (GameLog, "Log", 6779)];
+    for (i, &(panel, label, id)) in tabs.iter().enumerate() {
+        let spec = ButtonSpec {
+            x: rect.x + i as i32 * tab_width,
+            y: rect.y,
+            w: tab_width,
+            h: 3,
+            text: if state.side_panel == panel {
+                format!("[{}]", label)
+            } else {
+                label.to_string()
+            },
+            id,
+        };
+
+        if do_button(platform,
+                     &mut state.ui_context,
+                     &spec,
+                     left_mouse_pressed,
+                     left_mouse_released) {
+            state.side_panel = panel;
+        }
+    }
+
+    let body = SpecRect {
+        x: rect.x,
+        y: rect.y + 3,
+        w: rect.w,
+        h: rect.h - 3,
+    };
+
+    draw_rect(platform, body.x, body.y, body.w, body.h);
+
+    match state.side_panel {
+        DeclarationHistory => draw_declaration_history(platform, state, &body),
+        GameLog => draw_game_log(platform, state, &body, scroll_delta),
+    }
+}
+
+fn draw_declaration_history(platform: &Platform, state: &State, rect: &SpecRect) {
+    let mut y = rect.y + 1;
+    for record in declaration_records(state).iter() {
+        if y + 1 >= rect.y + rect.h - 1 {
+            break;
+        }
+
+        (platform.print_xy)(rect.x + 1,
+                            y,
+                            &format!("{:<11}{:>9}", record.subsuit.to_string(), score_text(record)));
+        (platform.print_xy)(rect.x + 1,
+                            y + 1,
+                            &format!(" {} {}",
+                                    if record.correct { "✓" } else { "✗" },
+                                    player_name(state, record.declarer)));
+
+        y += 2;
+    }
+}
+
+fn draw_game_log(platform: &Platform, state: &mut State, rect: &SpecRect, scroll_delta: i32) {
+    let text_width = (rect.w - 2) as usize;
+    let visible_lines = (rect.h - 2) as usize;
+
+    let mut lines = Vec::new();
+    for entry in state.history.iter() {
+        //continuation lines are indented so separate entries stand out
+        for (i, line) in wrap_text(&history_entry_text(state, entry), text_width - 1)
+                .into_iter()
+                .enumerate() {
+            lines.push(if i == 0 { line } else { format!(" {}", line) });
+        }
+    }
+
+    let max_scroll = lines.len().saturating_sub(visible_lines);
+    state.log_scroll = if scroll_delta < 0 {
+        state.log_scroll.saturating_add((-scroll_delta) as usize)
+    } else {
+        state.log_scroll.saturating_sub(scroll_delta as usize)
+    };
+    if state.log_scroll > max_scroll {
+        state.log_scroll = max_scroll;
+    }
+
+    let end = lines.len() - state.log_scroll;
+    let start = end.saturating_sub(visible_lines);
+
+    for (i, line) in lines[start..end].iter().enumerate() {
+        (platform.print_xy)(rect.x + 1, rect.y + 1 + i as i32, line);
+    }
+
+    if start > 0 {
+        (platform.print_xy)(rect.x + rect.w - 2, rect.y, "↑");
+    }
+    if state.log_scroll > 0 {
+        (platform.print_xy)(rect.x + rect.w - 2, rect.y + rect.h - 1, "↓");
+    }
+}
+
+//the opponents sit across the top of the menu and the player's team across the bottom
+fn draw_table(platform: &Platform, state: &State, menu: &SpecRect) {
+    let seat_width = menu.w / 3;
+
+    for (i, &opponent) in [OpponentZero, OpponentOne, OpponentTwo].iter().enumerate() {
+        draw_seat(platform,
+                  state,
+                  OpponentPlayer(opponent),
+                  (menu.x + i as i32 * seat_width, menu.y - table_row_height(state)),
+                  seat_width);
+    }
+
+    for (i, &teammate) in [ThePlayer, TeammateOne, TeammateTwo].iter().enumerate() {
+        draw_seat(platform,
+                  state,
+                  TeammatePlayer(teammate),
+                  (menu.x + i as i32 * seat_width, menu.y + menu.h),
+                  seat_width);
+    }
+}
+
+fn draw_seat(platform: &Platform, state: &State, seat: Player, (x, y): (i32, i32), width: i32) {
+    //a very narrow window can leave no room at all
+    let width = (width - 1).max(0) as usize;
+
+    let turn_marker = if state.current_player == Some(seat) {
+        "▶"
+    } else {
+        " "
+    };
+
+    let card_count = player_hand(state, seat).len();
+
+    let foreground = (platform.get_foreground)();
+    (platform.set_foreground)(match seat {
+                                  TeammatePlayer(_) => TEAMMATE_COLOR,
+                                  OpponentPlayer(_) => OPPONENT_COLOR,
+                              });
+
+    let count_text = format!("{} card{}", card_count, if card_count == 1 { "" } else { "s" });
+    let name_width = width.saturating_sub(count_text.len() + 2);
+
+    let heading = format!("{}{:<name_width$} {}",
+                          turn_marker,
+                          truncate(&player_name(state, seat), name_width),
+                          count_text,
+                          name_width = name_width);
+    (platform.print_xy)(x, y, &heading);
+
+    (platform.set_foreground)(foreground);
+
+    let detail_width = width.saturating_sub(1);
+
+    if state.reveal_hands {
+        let mut lines = wrap_text(&hand_summary(player_hand(state, seat)), detail_width);
+        if lines.len() > REVEALED_HAND_LINES {
+            lines.truncate(REVEALED_HAND_LINES);
+            let last = format!("{} …", lines[REVEALED_HAND_LINES - 1]);
+            lines[REVEALED_HAND_LINES - 1] = truncate(&last, detail_width);
+        }
+
+        for (i, line) in lines.iter().enumerate() {
+            (platform.print_xy)(x + 1, y + 1 + i as i32, line);
+        }
+    } else if let Some(detail) = last_action_text(state, seat) {
+        (platform.print_xy)(x + 1, y + 1, &truncate(&detail, detail_width));
+    }
+}
+
+//shown hands can be wider than a seat, so the table makes room for them to
+//wrap onto more lines
+fn table_row_height(state: &State) -> i32 {
+    if state.reveal_hands {
+        TABLE_ROW_HEIGHT + REVEALED_HAND_LINES as i32 - 1
+    } else {
+        TABLE_ROW_HEIGHT
+    }
+}
+
+//a hand grouped by suit, like "♣2 4 J ♥A 10"
+fn hand_summary(hand: &Hand) -> String {
+    let mut cards: Vec<(Suit, Value)> = hand.iter().map(|card| (card.suit, card.value)).collect();
+    cards.sort();
+
+    let mut result = String::new();
+    let mut last_suit = None;
+    for &(suit, value) in cards.iter() {
+        if last_suit != Some(suit) {
+            if last_suit.is_some() {
+                result.push(' ');
+            }
+            result.push_str(&suit.to_string());
+            last_suit = Some(suit);
+        } else {
+            result.push(' ');
+        }
+        result.push_str(&value.to_string());
+    }
+
+    result
+}
+
+fn last_action_text(state: &State, seat: Player) -> Option<String> {
+    for entry in state.history.iter().rev() {
+        match *entry {
+            Asked(ask_vector, suit, value, target_had_it) => {
+                let (asker, target) = ask_vector_players(ask_vector);
+
+                if asker == seat {
+                    return Some(format!("asked {} for {}{}: {}",
+                                        player_name(state, target),
+                                        value,
+                                        suit,
+                                        if target_had_it { "yes" } else { "no" }));
+                }
+            }
+            Declared(record) => {
+                if record.declarer == seat {
+                    return Some(format!("declared {}: {}",
+                                        record.subsuit,
+                                        if record.correct { "correct" } else { "wrong" }));
+                }
+            }
+            TurnPassed(_) => {}
+        }
+    }
+
+    None
+}
+
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() > width {
+        let mut result: String = text.chars().take(width.saturating_sub(1)).collect();
+        result.push('…');
+
+        result
+    } else {
+        text.to_string()
+    }
+}
+
+fn draw_notebook(platform: &Platform, state: &State, rect: &SpecRect) {
+    let memory = get_memory(state, state.notebook_seat);
+
+    let heading = match state.notebook_seat {
+        TeammatePlayer(ThePlayer) => {
+            "Deduction notebook: what you can work out from what you have seen".to_string()
+        }
+        seat => format!("{}'s memory: what they have worked out so far", player_name(state, seat)),
+    };
+    let width = rect.w.max(0) as usize;
+    (platform.print_xy)(rect.x, rect.y, &truncate(&heading, width));
+    (platform.print_xy)(rect.x,
+                        rect.y + 1,
+                        &truncate("Each box is You T1 T2 | O0 O1 O2    ● has it  · doesn't have \
+                                   it  ? unknown",
+                                  width));
+
+    //a half-suit's cards go over as many lines as it takes to leave room
+    //for the "Has some" column
+    let cards_per_line = ((rect.w - NOTEBOOK_CARDS_COLUMN - NOTEBOOK_HAS_SOME_WIDTH) /
+                          NOTEBOOK_CARD_WIDTH)
+        .clamp(1, 6);
+    let lines_per_subsuit = (6 + cards_per_line - 1) / cards_per_line;
+    let has_some_column = NOTEBOOK_CARDS_COLUMN + cards_per_line * NOTEBOOK_CARD_WIDTH;
+
+    (platform.print_xy)(rect.x + has_some_column, rect.y + 2, "Has some");
+
+    let records = declaration_records(state);
+
+    for (i, &subsuit) in SubSuit::all_values().iter().enumerate() {
+        let y = rect.y + 3 + i as i32 * lines_per_subsuit;
+
+        (platform.print_xy)(rect.x, y, &subsuit.to_string());
+
+        if subsuit_is_in_play(state, subsuit) {
+            let entries: Vec<String> = pairs_from_subsuit(subsuit)
+                .iter()
+                .map(|&(suit, value)| {
+                         format!("{:>3} {}  ",
+                                 value.to_string() + &suit.to_string(),
+                                 notebook_card_statuses(memory, suit, value))
+                     })
+                .collect();
+
+            for (line, cards) in entries.chunks(cards_per_line as usize).enumerate() {
+                (platform.print_xy)(rect.x + NOTEBOOK_CARDS_COLUMN,
+                                    y + line as i32,
+                                    &cards.concat());
+            }
+
+            let has_some = seat_status_string(|seat| if known_to_have_some_of(memory,
+                                                                                seat,
+                                                                                subsuit) {
+                                                  '!'
+                                              } else {
+                                                  '·'
+                                              });
+
+            (platform.print_xy)(rect.x + has_some_column, y, &has_some);
+        } else if let Some(record) = records.iter().find(|r| r.subsuit == subsuit) {
+            let declared = format!("declared by {}, {}",
+                                   player_name(state, record.declarer),
+                                   score_text(record));
+
+            (platform.print_xy)(rect.x + NOTEBOOK_CARDS_COLUMN,
+                                y,
+                                &truncate(&declared,
+                                          width.saturating_sub(NOTEBOOK_CARDS_COLUMN as usize)));
+        }
+    }
+}
+
+const NOTEBOOK_CARDS_COLUMN: i32 = 11;
+//one card and its six boxes, like ` 5♣ ·??|???  `
+const NOTEBOOK_CARD_WIDTH: i32 = 13;
+const NOTEBOOK_HAS_SOME_WIDTH: i32 = 8;
+
+fn notebook_card_statuses(memory: &Memory, suit: Suit, value: Value) -> String {
+    let holder = ALL_SEATS
+        .iter()
+        .find(|&&seat| known_to_have(memory, seat, (suit, value)))
+        .cloned();
+
+    seat_status_string(|seat| match holder {
+                           Some(h) if h == seat => '●',
+                           Some(_) => '·',
+                           None if !not_known_not_to_have(memory, seat, (suit, value)) => '·',
+                           None => '?',
+                       })
+}
+
+fn seat_status_string<F: Fn(Player) -> char>(status: F) -> String {
+    let mut result = String::new();
+
+    for (i, &seat) in ALL_SEATS.iter().enumerate() {
+        if i == 3 {
+            result.push('|');
+        }
+        result.push(status(seat));
+    }
+
+    result
+}
+
+fn known_to_have_some_of(memory: &Memory, target_player: Player, subsuit: SubSuit) -> bool {
+    if let Some(knowledge) = memory.get(&target_player) {
+        for &fact in knowledge.facts.iter() {
+            if let KnownToHaveSomeOf(known_subsuit) = fact {
+                if known_subsuit == subsuit {
+                    return true;
+                }
+            }
+        }
+    }
+
+    pairs_from_subsuit(subsuit)
+        .iter()
+        .any(|&pair| known_to_have(memory, target_player, pair))
+}
+
+fn history_entry_text(state: &State, entry: &HistoryEntry) -> String {
+    match *entry {
+        Asked(ask_vector, suit, value, target_had_it) => {
+            let (asker, target) = ask_vector_players(ask_vector);
+
+            format!("{} asked {} for the {}{} — {}",
+                    player_name(state, asker),
+                    player_name(state, target),
+                    value,
+                    suit,
+                    if target_had_it { "yes" } else { "no" })
+        }
+        TurnPassed(TeammatePlayer(ThePlayer)) => "Your turn".to_string(),
+        TurnPassed(player) => format!("{}'s turn", player_name(state, player)),
+        Declared(record) => {
+            let mut text = format!("{} declared {}: {}, {}",
+                                   player_name(state, record.declarer),
+                                   record.subsuit,
+                                   if record.correct { "correct" } else { "wrong" },
+                                   score_text(&record));
+
+            let pairs = pairs_from_subsuit(record.subsuit);
+            for (i, &(suit, value)) in pairs.iter().enumerate() {
+                if record.claimed[i] != record.actual[i] {
+                    text.push_str(&format!("; {}{} was with {}",
+                                           value,
+                                           suit,
+                                           player_name(state, record.actual[i])));
+                }
+            }
+
+            text
+        }
+    }
+}
+
+fn declaration_records(state: &State) -> Vec<DeclarationRecord> {
+    state.history
+        .iter()
+        .filter_map(|entry| match *entry {
+                        Declared(record) => Some(record),
+                        _ => None,
+                    })
+        .collect()
+}
+
+fn wrap_text(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split_whitespace() {
+        let line_len = line.chars().count();
+
+        if line_len > 0 && line_len + 1 + word.chars().count() > width {
+            lines.push(line);
+            line = String::new();
+        }
+
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(word);
+    }
+
+    if !line.is_empty() {
+        lines.push(line);
+    }
+
+    lines
+}
+
+fn score_text(record: &DeclarationRecord) -> &'static str {
+    if record.cancelled {
+        "cancelled"
+    } else if record.scored_for_player_team() {
+        "Us +1"
+    } else {
+        "Them +1"
+    }
+}
+
 fn guess_declaration(state: &mut State) -> Declaration {
 
     let mut declarations = Vec::new();
@@ -503,8 +2354,7 @@ fn guess_declaration(state: &mut State) -> Declaration {
                     continue;
                 }
                 for current_player in Player::all_values() {
-                    if let Some(knowledge) = get_memory(state, player)
-                           .and_then(|m| m.get(&current_player)) {
+                    if let Some(knowledge) = get_memory(state, player).get(&current_player) {
                         if knowledge.model_hand.contains(&Known(suit, value)) {
                             guessed_owners.push(player);
                             continue 'pairs;
@@ -526,31 +2376,263 @@ fn guess_declaration(state: &mut State) -> Declaration {
                     } else {
                         None
                     }
-                }
-                OpponentPlayer(o) => {
-                    if let Some(opponents) = get_opponent_declaration_array(guessed_owners) {
-                        Some(DeclareStep3(OpponentDInfo(o, subsuit, opponents)))
+                }
+                OpponentPlayer(o) => {
+                    if let Some(opponents) = get_opponent_declaration_array(guessed_owners) {
+                        Some(DeclareStep3(OpponentDInfo(o, subsuit, opponents)))
+                    } else {
+                        None
+                    }
+                }
+            };
+
+            if let Some(actual_info) = possible_info {
+                declarations.push(actual_info);
+            }
+        }
+    }
+
+    let len = declarations.len();
+    if len > 0 {
+        if let Some(&declaration) = declarations.get(state.rng.gen_range(0, len)) {
+            declaration
+        } else {
+            DeclareStep3(OpponentDInfo(OpponentZero, LowClubs, [OpponentZero; 6]))
+        }
+    } else {
+        DeclareStep3(OpponentDInfo(OpponentZero, HighSpades, [OpponentZero; 6]))
+    }
+}
+
+//handing the seat over abandons anything the player was halfway through choosing
+fn toggle_autopilot(state: &mut State) {
+    if state.spectating {
+        return;
+    }
+
+    state.autopilot = !state.autopilot;
+
+    if state.autopilot {
+        state.menu_state = match state.menu_state {
+            AskStep1 | AskStep2(_) | AskStep3(_, _) => Main,
+            other => other,
+        };
+        state.declaration = match state.declaration {
+            Some(DeclareStep1) |
+            Some(DeclareStep2(_, _)) => None,
+            other => other,
+        };
+        state.selected_card = None;
+    }
+}
+
+fn draw_replay_controls(platform: &Platform,
+                        state: &mut State,
+                        inner: &SpecRect,
+                        left_mouse_pressed: bool,
+                        left_mouse_released: bool) {
+    let (step, length, last_move) = match state.replay {
+        Some(ref replay) => {
+            let last_move = replay.step
+                .checked_sub(1)
+                .and_then(|i| {
+                              replay.record
+                                  .moves
+                                  .iter()
+                                  .filter(|entry| !matches!(**entry, TurnPassed(_)))
+                                  .nth(i)
+                          })
+                .map(|entry| history_entry_text(state, entry));
+
+            (replay.step, replay_length(&replay.record), last_move)
+        }
+        None => return,
+    };
+
+    print_horizontally_centered_line(platform,
+                                     inner,
+                                     &format!("Replay: move {} of {}", step, length),
+                                     inner.y + 1);
+    print_horizontally_centered_line(platform,
+                                     inner,
+                                     &last_move.unwrap_or_else(|| "The deal".to_string()),
+                                     inner.y + 3);
+    print_horizontally_centered_line(platform,
+                                     inner,
+                                     "Left and Right step, Home and End jump",
+                                     inner.y + 5);
+
+    let button_width = 14;
+    let back_button = ButtonSpec {
+        x: inner.x + inner.w / 2 - button_width - 1,
+        y: inner.y + 7,
+        w: button_width,
+        h: 3,
+        text: "← Back".to_string(),
+        id: 1228,
+    };
+    let forward_button = ButtonSpec {
+        x: inner.x + inner.w / 2 + 1,
+        text: "Forward →".to_string(),
+        id: 1229,
+        ..back_button
+    };
+
+    let size = (platform.size)();
+
+    if do_button(platform,
+                 &mut state.ui_context,
+                 &back_button,
+                 left_mouse_pressed,
+                 left_mouse_released) {
+        go_to_replay_step(state, size, -1);
+    }
+
+    if do_button(platform,
+                 &mut state.ui_context,
+                 &forward_button,
+                 left_mouse_pressed,
+                 left_mouse_released) {
+        go_to_replay_step(state, size, 1);
+    }
+}
+
+fn draw_spectator_options(platform: &Platform,
+                          state: &mut State,
+                          left_mouse_pressed: bool,
+                          left_mouse_released: bool) {
+    let hands_text = format!("Hands: {} (H)",
+                             if state.reveal_hands { "shown" } else { "hidden" });
+    if do_text_button(platform,
+                      &mut state.ui_context,
+                      (MENU_OFFSET, 0),
+                      &hands_text,
+                      2337,
+                      left_mouse_pressed,
+                      left_mouse_released) {
+        state.reveal_hands = !state.reveal_hands;
+    }
+
+    let memory_text = format!("Notebook: {} (M)", player_name(state, state.notebook_seat));
+    if do_text_button(platform,
+                      &mut state.ui_context,
+                      (MENU_OFFSET + hands_text.chars().count() as i32 + 2, 0),
+                      &memory_text,
+                      2338,
+                      left_mouse_pressed,
+                      left_mouse_released) {
+        next_notebook_seat(state);
+    }
+}
+
+//moves the notebook on to the next seat's memory and shows it
+fn next_notebook_seat(state: &mut State) {
+    let seat = state.notebook_seat;
+    state.notebook_seat = ALL_SEATS
+        .iter()
+        .skip_while(|&&p| p != seat)
+        .nth(1)
+        .cloned()
+        .unwrap_or(ALL_SEATS[0]);
+    state.notebook_open = true;
+}
+
+//F1 steps through each seat's memory and then closes the overlay
+#[cfg(feature = "debug")]
+fn next_debug_memory_overlay(overlay: Option<DebugOverlay>) -> Option<DebugOverlay> {
+    match overlay {
+        Some(DebugOverlay::RawMemory(seat)) => {
+            ALL_SEATS
+                .iter()
+                .skip_while(|&&p| p != seat)
+                .nth(1)
+                .map(|&p| DebugOverlay::RawMemory(p))
+        }
+        _ => Some(DebugOverlay::RawMemory(ALL_SEATS[0])),
+    }
+}
+
+#[cfg(feature = "debug")]
+fn draw_debug_overlay(platform: &Platform,
+                      state: &State,
+                      rect: &SpecRect,
+                      overlay: DebugOverlay) {
+    let x = rect.x + 1;
+    let width = (rect.w - 2) as usize;
+    let mut y = rect.y;
+
+    match overlay {
+        DebugOverlay::RawMemory(owner) => {
+            (platform.print_xy)(x,
+                                y,
+                                &format!("DEBUG {}'s memory    F1 next seat  F2 true hands",
+                                        player_name(state, owner)));
+            y += 2;
+
+            let memory = get_memory(state, owner);
+
+            for &seat in ALL_SEATS.iter() {
+                (platform.print_xy)(x, y, &format!("{} ({})", player_name(state, seat), seat));
+                y += 1;
+
+                let knowledge = match memory.get(&seat) {
+                    Some(knowledge) => knowledge,
+                    None => {
+                        (platform.print_xy)(x + 2, y, "no entry");
+                        y += 1;
+                        continue;
+                    }
+                };
+
+                let model_hand: Vec<String> = knowledge
+                    .model_hand
+                    .iter()
+                    .map(|&slot| match slot {
+                             Known(suit, value) => format!("{}{}", value, suit),
+                             Unknown => "?".to_string(),
+                         })
+                    .collect();
+                (platform.print_xy)(x + 2,
+                                    y,
+                                    &truncate(&format!("model hand: {}", model_hand.join(" ")),
+                                              width - 2));
+                y += 1;
+
+                let facts: Vec<String> = knowledge
+                    .facts
+                    .iter()
+                    .map(|&fact| match fact {
+                             KnownNotToHave(suit, value) => format!("-{}{}", value, suit),
+                             KnownToHaveSomeOf(subsuit) => format!("+[{}]", subsuit),
+                         })
+                    .collect();
+                let lines = wrap_text(&format!("facts: {}", facts.join(" ")), width - 3);
+                for (i, line) in lines.iter().take(2).enumerate() {
+                    let line = if i == 1 && lines.len() > 2 {
+                        format!("{} …", line)
                     } else {
-                        None
-                    }
-                }
-            };
+                        line.clone()
+                    };
 
-            if let Some(actual_info) = possible_info {
-                declarations.push(actual_info);
+                    (platform.print_xy)(x + 2, y, &line);
+                    y += 1;
+                }
             }
         }
-    }
-
-    let len = declarations.len();
-    if len > 0 {
-        if let Some(&declaration) = declarations.get(state.rng.gen_range(0, len)) {
-            declaration
-        } else {
-            DeclareStep3(OpponentDInfo(OpponentZero, LowClubs, [OpponentZero; 6]))
+        DebugOverlay::TrueHands => {
+            (platform.print_xy)(x, y, "DEBUG true hands    F1 memories  F2 close");
+            y += 2;
+
+            for &seat in ALL_SEATS.iter() {
+                (platform.print_xy)(x,
+                                    y,
+                                    &truncate(&format!("{:<12} {}",
+                                                      player_name(state, seat),
+                                                      hand_summary(player_hand(state, seat))),
+                                              width));
+                y += 1;
+            }
         }
-    } else {
-        DeclareStep3(OpponentDInfo(OpponentZero, HighSpades, [OpponentZero; 6]))
     }
 }
 
@@ -562,6 +2644,34 @@ fn show_quit_screen(platform: &Platform,
     let mid_y = inner.y + (inner.h / 2);
     print_horizontally_centered_line(platform, &inner, "Press Esc again to Quit", mid_y);
 
+    let save_record = ButtonSpec {
+        x: inner.x + ((inner.w - 14) / 2),
+        y: mid_y - 6,
+        w: 14,
+        h: 3,
+        text: "Save record".to_string(),
+        id: 1227,
+    };
+
+    //a replay only has the moves up to where it's been stepped to
+    if state.replay.is_none() &&
+       do_button(platform,
+                 &mut state.ui_context,
+                 &save_record,
+                 left_mouse_pressed,
+                 left_mouse_released) {
+        let saved = game_record::save(state.config_dir.as_deref(), &game_record(state));
+
+        state.notice = Some(match saved {
+                                Ok(path) => format!("Saved to {}", path.display()),
+                                Err(problem) => problem,
+                            });
+    }
+
+    if let Some(ref notice) = state.notice {
+        print_horizontally_centered_line(platform, &inner, notice, mid_y - 2);
+    }
+
     let resume = ButtonSpec {
         x: inner.x + ((inner.w - 14) / 2),
         y: mid_y + 3,
@@ -577,6 +2687,43 @@ fn show_quit_screen(platform: &Platform,
                  left_mouse_pressed,
                  left_mouse_released) {
         state.menu_state = Main;
+        state.notice = None;
+    }
+
+    let main_menu = ButtonSpec {
+        x: inner.x + ((inner.w - 14) / 2),
+        y: mid_y + 7,
+        w: 14,
+        h: 3,
+        text: "Main menu".to_string(),
+        id: 1225,
+    };
+
+    if do_button(platform,
+                 &mut state.ui_context,
+                 &main_menu,
+                 left_mouse_pressed,
+                 left_mouse_released) {
+        state.notice = None;
+        autosave(state);
+        state.title_screen = true;
+        state.replay = None;
+    }
+
+    let settings = ButtonSpec {
+        y: mid_y + 11,
+        text: "Settings".to_string(),
+        id: 1226,
+        ..main_menu
+    };
+
+    if do_button(platform,
+                 &mut state.ui_context,
+                 &settings,
+                 left_mouse_pressed,
+                 left_mouse_released) {
+        state.menu_state = Main;
+        state.settings_open = true;
     }
 }
 
@@ -598,17 +2745,11 @@ fn get_opposite_team(player: Player) -> Vec<Player> {
 }
 
 fn get_ask_info(state: &mut State, player: Player) -> Option<(AskVector, Suit, Value)> {
-    let memory = match player {
-        TeammatePlayer(ThePlayer) => {
-            println!("Cannot return a reference to the player's memory");
-            return Some((ToOpponent(ThePlayer, OpponentZero), Spades, Ace));
-        }
-        TeammatePlayer(TeammateOne) => &state.teammate_1_memory,
-        TeammatePlayer(TeammateTwo) => &state.teammate_2_memory,
-        OpponentPlayer(OpponentZero) => &state.opponent_1_memory,
-        OpponentPlayer(OpponentOne) => &state.opponent_2_memory,
-        OpponentPlayer(OpponentTwo) => &state.opponent_3_memory,
-    };
+    if state.settings.difficulty == Easy {
+        return get_random_ask_info(state, player);
+    }
+
+    let memory = get_memory(state, player);
 
     let mut other_team: Vec<Player> = get_opposite_team(player);
     other_team = other_team
@@ -634,6 +2775,21 @@ fn get_ask_info(state: &mut State, player: Player) -> Option<(AskVector, Suit, V
         }
     }
 
+    if state.settings.difficulty == Hard {
+        //players can only ask for cards in half-suits they hold
+        for pair in possible_pairs.iter() {
+            let subsuit = subsuit_from_pair(pair.0, pair.1);
+
+            for &target_player in other_team.iter() {
+                if not_known_not_to_have(memory, target_player, *pair) &&
+                   known_to_have_some_of(memory, target_player, subsuit) {
+                    return make_ask_vector(player, target_player)
+                               .map(|vector| (vector, pair.0, pair.1));
+                }
+            }
+        }
+    }
+
     //TODO don't ask an opponent a quesion if they would win a suit,
     //i.e. you know that they know where all the cards of a suit are
     //but some are in one of your teammates hands. Instead try to ask
@@ -666,6 +2822,24 @@ fn get_ask_info(state: &mut State, player: Player) -> Option<(AskVector, Suit, V
 
 }
 
+//asks for any card that can be asked for, ignoring everything learned so far
+fn get_random_ask_info(state: &mut State, player: Player) -> Option<(AskVector, Suit, Value)> {
+    let other_team: Vec<Player> = get_opposite_team(player)
+        .into_iter()
+        .filter(|&p| !player_hand(state, p).is_empty())
+        .collect();
+    let possible_pairs = get_possible_target_pairs(player_hand(state, player));
+
+    if other_team.is_empty() || possible_pairs.is_empty() {
+        return None;
+    }
+
+    let target_player = other_team[state.rng.gen_range(0, other_team.len())];
+    let (suit, value) = possible_pairs[state.rng.gen_range(0, possible_pairs.len())];
+
+    make_ask_vector(player, target_player).map(|vector| (vector, suit, value))
+}
+
 fn known_to_have(memory: &Memory, target_player: Player, pair: (Suit, Value)) -> bool {
     if let Some(knowledge) = memory.get(&target_player) {
         for &card in knowledge.model_hand.iter() {
@@ -822,6 +2996,13 @@ fn player_hand(state: &State, player: Player) -> &Hand {
     }
 }
 
+fn player_hand_mut(state: &mut State, player: Player) -> &mut Hand {
+    match player {
+        TeammatePlayer(t) => teammate_hand_mut(state, t),
+        OpponentPlayer(o) => opponent_hand_mut(state, o),
+    }
+}
+
 fn opponent_hand(state: &State, opponent: Opponent) -> &Hand {
     match opponent {
         OpponentZero => &state.opponent_1,
@@ -834,6 +3015,138 @@ fn opponent_hand(state: &State, opponent: Opponent) -> &Hand {
 const HAND_ARROW_WIDTH: i32 = 4;
 const HAND_ARROW_HEIGHT: i32 = 3;
 const DECLARE_BUTTON_WIDTH: i32 = 11;
+const NOTEBOOK_BUTTON_WIDTH: i32 = 12;
+
+//the input shared by every screen that is drawn with the IMGUI
+#[derive(Default)]
+struct UiInput {
+    left_mouse_pressed: bool,
+    left_mouse_released: bool,
+    keyboard: KeyboardInput,
+}
+
+fn handle_ui_event(event: &Event, input: &mut UiInput) {
+    match *event {
+        Event::KeyPressed {
+            key: KeyCode::Tab,
+            ctrl: _,
+            shift,
+        } => {
+            input.keyboard.focus_delta = if shift { -1 } else { 1 };
+        }
+        Event::KeyPressed {
+            key: KeyCode::Right,
+            ctrl: _,
+            shift: _,
+        } |
+        Event::KeyPressed {
+            key: KeyCode::Down,
+            ctrl: _,
+            shift: _,
+        } => {
+            input.keyboard.focus_delta = 1;
+        }
+        Event::KeyPressed {
+            key: KeyCode::Left,
+            ctrl: _,
+            shift: _,
+        } |
+        Event::KeyPressed {
+            key: KeyCode::Up,
+            ctrl: _,
+            shift: _,
+        } => {
+            input.keyboard.focus_delta = -1;
+        }
+        Event::KeyPressed {
+            key: KeyCode::Enter,
+            ctrl: _,
+            shift: _,
+        } |
+        Event::KeyPressed {
+            key: KeyCode::NumEnter,
+            ctrl: _,
+            shift: _,
+        } => {
+            input.keyboard.activate = true;
+        }
+        Event::KeyPressed {
+            key: KeyCode::Space,
+            ctrl: _,
+            shift: _,
+        } => {
+            input.keyboard.activate = true;
+            input.keyboard.typed = Some(' ');
+        }
+        Event::KeyPressed {
+            key: KeyCode::Backspace,
+            ctrl: _,
+            shift: _,
+        } => {
+            input.keyboard.back = true;
+        }
+        Event::KeyPressed {
+            key: KeyCode::MouseLeft,
+            ctrl: _,
+            shift: _,
+        } => {
+            input.left_mouse_pressed = true;
+        }
+        Event::KeyReleased {
+            key: KeyCode::MouseLeft,
+            ctrl: _,
+            shift: _,
+        } => {
+            input.left_mouse_released = true;
+        }
+        Event::KeyPressed {
+            key,
+            ctrl: false,
+            shift,
+        } => {
+            input.keyboard.typed = char_from_key(key, shift);
+        }
+        _ => (),
+    }
+}
+
+fn char_from_key(key: KeyCode, shift: bool) -> Option<char> {
+    let c = match key {
+        KeyCode::A => 'a',
+        KeyCode::B => 'b',
+        KeyCode::C => 'c',
+        KeyCode::D => 'd',
+        KeyCode::E => 'e',
+        KeyCode::F => 'f',
+        KeyCode::G => 'g',
+        KeyCode::H => 'h',
+        KeyCode::I => 'i',
+        KeyCode::J => 'j',
+        KeyCode::K => 'k',
+        KeyCode::L => 'l',
+        KeyCode::M => 'm',
+        KeyCode::N => 'n',
+        KeyCode::O => 'o',
+        KeyCode::P => 'p',
+        KeyCode::Q => 'q',
+        KeyCode::R => 'r',
+        KeyCode::S => 's',
+        KeyCode::T => 't',
+        KeyCode::U => 'u',
+        KeyCode::V => 'v',
+        KeyCode::W => 'w',
+        KeyCode::X => 'x',
+        KeyCode::Y => 'y',
+        KeyCode::Z => 'z',
+        KeyCode::Row0 | KeyCode::Num0 => '0',
+        KeyCode::Minus => '-',
+        KeyCode::Period => '.',
+        KeyCode::Space => ' ',
+        _ => return number_from_key(key).map(|n| (b'0' + n) as char),
+    };
+
+    Some(if shift { c.to_ascii_uppercase() } else { c })
+}
 
 fn cross_mode_event_handling(platform: &Platform, state: &mut State, event: &Event) {
     match *event {
@@ -842,41 +3155,208 @@ fn cross_mode_event_handling(platform: &Platform, state: &mut State, event: &Eve
             ctrl: true,
             shift: _,
         } => {
-            println!("reset");
-            *state = new_state((platform.size)());
+            restart(state, (platform.size)());
         }
         _ => (),
     }
 }
 
 const MENU_OFFSET: i32 = 2;
-const MENU_TOP_HEIGHT_OFFSET: i32 = 1;
-const MENU_BOTTOM_HEIGHT_OFFSET: i32 = HAND_HEIGHT_OFFSET + 2;
+const MENU_TOP_HEIGHT_OFFSET: i32 = 1 + TABLE_ROW_HEIGHT;
+const MENU_BOTTOM_HEIGHT_OFFSET: i32 = HAND_HEIGHT_OFFSET + 2 + TABLE_ROW_HEIGHT;
+//each seat at the table takes up this many lines
+const TABLE_ROW_HEIGHT: i32 = 2;
+const REVEALED_HAND_LINES: usize = 2;
 const HAND_WINDOW_SIZE: usize = 8;
+const SIDE_PANEL_WIDTH: i32 = 28;
+const LOG_PAGE_SIZE: i32 = 5;
 
 fn draw(platform: &Platform, state: &State) {
     let size = (platform.size)();
 
-    let mut x = CARD_OFFSET;
     let y = hand_height(size.height);
 
+    for (index, x) in hand_layout(state) {
+        let card = &state.player[index];
+
+        //the selected card sticks up out of the hand
+        let lift = if is_selected(state, card) { 1 } else { 0 };
+
+        draw_card(platform, (x, y - lift), card);
+    }
+
+    (platform.print_xy)(size.width - 9,
+                        size.height - 3,
+                        &format!("{}:{}", state.player_points, state.opponent_points));
+    (platform.print_xy)(size.width - 10, size.height - 2, "Us Them");
+}
+
+//the index and x position of each visible card in the player's hand
+fn hand_layout(state: &State) -> Vec<(usize, i32)> {
+    let mut result = Vec::new();
+
+    let mut x = CARD_OFFSET;
+    let mut previous_subsuit = None;
+
     for i in 0..HAND_WINDOW_SIZE {
         let index = i + state.card_offset;
 
         if let Some(card) = state.player.get(index) {
-            draw_card(platform, (x, y), card);
+            let subsuit = subsuit_from_pair(card.suit, card.value);
+
+            if state.hand_order == GroupedByHalfSuit && previous_subsuit.is_some() &&
+               previous_subsuit != Some(subsuit) {
+                x += 1;
+            }
+            previous_subsuit = Some(subsuit);
+
+            result.push((index, x));
             x += CARD_OFFSET_DELTA;
         } else {
             break;
         }
     }
 
-    (platform.print_xy)(size.width - 9,
-                        size.height - 3,
-                        &format!("{}:{}", state.player_points, state.opponent_points));
-    (platform.print_xy)(size.width - 10, size.height - 2, "Us Them");
+    result
+}
+
+fn is_selected(state: &State, card: &Card) -> bool {
+    state.selected_card == Some((card.suit, card.value))
+}
+
+//lets the player select cards, and drag them to a new position
+fn do_hand(platform: &Platform,
+           state: &mut State,
+           left_mouse_pressed: bool,
+           left_mouse_released: bool) {
+    let size = (platform.size)();
+    let y = hand_height(size.height) - 1;
+    let mouse_pos = (platform.mouse_position)();
+
+    let layout = hand_layout(state);
+    let mut hovered = None;
+    for (i, &(index, x)) in layout.iter().enumerate() {
+        //all but the last card are mostly covered by the next one
+        let visible_width = match layout.get(i + 1) {
+            Some(&(_, next_x)) => next_x - x,
+            None => CARD_WIDTH,
+        };
+
+        if inside_rect(mouse_pos, x, y, visible_width, CARD_HEIGHT + 1) {
+            hovered = Some(index);
+        }
+    }
+
+    let mut clicked = None;
+    let mut dragged = None;
+
+    for &(index, _) in layout.iter() {
+        let id = HAND_CARD_BASE_ID + index as UiId;
+        let context = &mut state.ui_context;
+
+        if context.active == id {
+            if left_mouse_released {
+                match hovered {
+                    Some(target) if target == index => clicked = Some(index),
+                    Some(target) => dragged = Some((index, target)),
+                    None => {}
+                }
+
+                context.set_not_active();
+            }
+        } else if context.hot == id && left_mouse_pressed {
+            context.set_active(id);
+        }
+
+        if hovered == Some(index) {
+            context.set_next_hot(id);
+        }
+
+        context.set_focusable(id);
+        if context.is_focused(id) && context.keyboard.activate {
+            clicked = Some(index);
+        }
+    }
+
+    if let Some(index) = clicked {
+        let card = &state.player[index];
+        state.selected_card = if is_selected(state, card) {
+            None
+        } else {
+            Some((card.suit, card.value))
+        };
+    }
+
+    if let Some((from, to)) = dragged {
+        let card = state.player.remove(from);
+        state.player.insert(to, card);
+        state.hand_order = Manual;
+    }
+}
+
+fn shift_selected_card(state: &mut State, delta: i32) {
+    let selected_index = state.selected_card.and_then(|(suit, value)| {
+        state.player.iter().position(|c| c.suit == suit && c.value == value)
+    });
+
+    if let Some(index) = selected_index {
+        let new_index = index as i32 + delta;
+
+        if new_index >= 0 && (new_index as usize) < state.player.len() {
+            let new_index = new_index as usize;
+            state.player.swap(index, new_index);
+            state.hand_order = Manual;
+
+            //keep the selected card in view
+            if new_index < state.card_offset {
+                state.card_offset = new_index;
+            } else if new_index >= state.card_offset + HAND_WINDOW_SIZE {
+                state.card_offset = new_index + 1 - HAND_WINDOW_SIZE;
+            }
+        }
+    }
+}
+
+fn sort_player_hand(state: &mut State) {
+    match state.hand_order {
+        AutoSorted => state.player.sort(),
+        GroupedByHalfSuit => {
+            //the half-suits the player has the most of come first
+            let mut counts = HashMap::new();
+            for card in state.player.iter() {
+                *counts.entry(subsuit_from_pair(card.suit, card.value)).or_insert(0) += 1;
+            }
+
+            state.player.sort_by(|a, b| {
+                let a_subsuit = subsuit_from_pair(a.suit, a.value);
+                let b_subsuit = subsuit_from_pair(b.suit, b.value);
+
+                counts[&b_subsuit]
+                    .cmp(&counts[&a_subsuit])
+                    .then(u8::from(a_subsuit).cmp(&u8::from(b_subsuit)))
+                    .then(a.cmp(b))
+            });
+        }
+        Manual => {}
+    }
 }
 
+fn add_to_hand(state: &mut State, player: Player, card: Card) {
+    let is_the_player = player == TeammatePlayer(ThePlayer);
+    let hand = player_hand_mut(state, player);
+
+    //in manual order this puts the card roughly where it would go if sorted
+    if let Err(insertion_index) = hand.binary_search(&card) {
+        hand.insert(insertion_index, card);
+    }
+
+    if is_the_player {
+        sort_player_hand(state);
+    }
+}
+
+const HAND_CARD_BASE_ID: UiId = 8000;
+
 pub struct SpecRect {
     pub x: i32,
     pub y: i32,
@@ -890,10 +3370,6 @@ impl AsRef<SpecRect> for SpecRect {
     }
 }
 
-//NOTE(Ryan1729): The AsRef impl below relies on ButtonSpec having
-// the same ordering for x,y,w and h as SpecRect, and that they are
-// at the top!
-#[repr(C)]
 pub struct ButtonSpec {
     pub x: i32,
     pub y: i32,
@@ -903,9 +3379,14 @@ pub struct ButtonSpec {
     pub id: i32,
 }
 
-impl AsRef<SpecRect> for ButtonSpec {
-    fn as_ref(&self) -> &SpecRect {
-        unsafe { std::mem::transmute::<&ButtonSpec, &SpecRect>(self) }
+impl ButtonSpec {
+    fn rect(&self) -> SpecRect {
+        SpecRect {
+            x: self.x,
+            y: self.y,
+            w: self.w,
+            h: self.h,
+        }
     }
 }
 
@@ -946,7 +3427,7 @@ fn draw_ask_opponent_menu(platform: &Platform,
         y: rect.y,
         w: button_width,
         h: rect.h,
-        text: "OpponentZero".to_string(),
+        text: opponent_name(state, OpponentZero),
         id: 123,
     };
 
@@ -954,12 +3435,14 @@ fn draw_ask_opponent_menu(platform: &Platform,
                  &mut state.ui_context,
                  &opponent_zero,
                  left_mouse_pressed,
-                 left_mouse_released) {
+                 left_mouse_released) || shortcut_pressed(&state.ui_context, 1) {
         state.menu_state = AskStep2(OpponentZero);
     }
 
+    draw_shortcut_hint(platform, &opponent_zero, 1);
+
     print_horizontally_centered_line(platform,
-                                     &opponent_zero,
+                                     &opponent_zero.rect(),
                                      &format!("{} cards", state.opponent_1.len()),
                                      opponent_zero.y + (opponent_zero.h / 2) + 1);
 
@@ -968,7 +3451,7 @@ fn draw_ask_opponent_menu(platform: &Platform,
         y: rect.y,
         w: button_width,
         h: rect.h,
-        text: "OpponentOne".to_string(),
+        text: opponent_name(state, OpponentOne),
         id: 234,
     };
 
@@ -976,12 +3459,14 @@ fn draw_ask_opponent_menu(platform: &Platform,
                  &mut state.ui_context,
                  &opponent_one,
                  left_mouse_pressed,
-                 left_mouse_released) {
+                 left_mouse_released) || shortcut_pressed(&state.ui_context, 2) {
         state.menu_state = AskStep2(OpponentOne);
     }
 
+    draw_shortcut_hint(platform, &opponent_one, 2);
+
     print_horizontally_centered_line(platform,
-                                     &opponent_one,
+                                     &opponent_one.rect(),
                                      &format!("{} cards", state.opponent_2.len()),
                                      opponent_one.y + (opponent_one.h / 2) + 1);
 
@@ -990,7 +3475,7 @@ fn draw_ask_opponent_menu(platform: &Platform,
         y: rect.y,
         w: button_width,
         h: rect.h,
-        text: "OpponentTwo".to_string(),
+        text: opponent_name(state, OpponentTwo),
         id: 345,
     };
 
@@ -998,17 +3483,91 @@ fn draw_ask_opponent_menu(platform: &Platform,
                  &mut state.ui_context,
                  &opponent_two,
                  left_mouse_pressed,
-                 left_mouse_released) {
+                 left_mouse_released) || shortcut_pressed(&state.ui_context, 3) {
         state.menu_state = AskStep2(OpponentTwo);
     }
 
+    draw_shortcut_hint(platform, &opponent_two, 3);
+
     print_horizontally_centered_line(platform,
-                                     &opponent_two,
+                                     &opponent_two.rect(),
                                      &format!("{} cards", state.opponent_3.len()),
                                      opponent_two.y + (opponent_two.h / 2) + 1);
 }
 
 
+//Lets the player go back to an earlier step of asking for a card
+fn draw_ask_breadcrumbs(platform: &Platform,
+                        state: &mut State,
+                        rect: &SpecRect,
+                        left_mouse_pressed: bool,
+                        left_mouse_released: bool) {
+    let previous_step = match state.menu_state {
+        AskStep2(_) => AskStep1,
+        AskStep3(opponent, _) => AskStep2(opponent),
+        _ => Main,
+    };
+
+    let back_text = "← Back";
+    if do_text_button(platform,
+                      &mut state.ui_context,
+                      (rect.x, rect.y),
+                      back_text,
+                      7000,
+                      left_mouse_pressed,
+                      left_mouse_released) || state.ui_context.keyboard.back {
+        state.menu_state = previous_step;
+        return;
+    }
+
+    let mut crumbs = vec![("Ask".to_string(), AskStep1)];
+    match state.menu_state {
+        AskStep2(opponent) => {
+            crumbs.push((opponent_name(state, opponent), AskStep2(opponent)));
+        }
+        AskStep3(opponent, subsuit) => {
+            crumbs.push((opponent_name(state, opponent), AskStep2(opponent)));
+            crumbs.push((subsuit.to_string(), AskStep3(opponent, subsuit)));
+        }
+        _ => {}
+    }
+
+    let mut x = rect.x + back_text.chars().count() as i32 + 2;
+    (platform.print_xy)(x - 1, rect.y, "│");
+    x += 1;
+
+    let last = crumbs.len() - 1;
+    for (i, (text, step)) in crumbs.into_iter().enumerate() {
+        if i == last {
+            //there's no point in clicking on the step we're already on
+            (platform.print_xy)(x, rect.y, &text);
+        } else {
+            if do_text_button(platform,
+                              &mut state.ui_context,
+                              (x, rect.y),
+                              &text,
+                              7001 + i as UiId,
+                              left_mouse_pressed,
+                              left_mouse_released) {
+                state.menu_state = step;
+            }
+
+            (platform.print_xy)(x + text.chars().count() as i32, rect.y, " › ");
+        }
+
+        x += text.chars().count() as i32 + 3;
+    }
+}
+
+fn below_breadcrumbs(rect: &SpecRect) -> SpecRect {
+    SpecRect {
+        x: rect.x,
+        y: rect.y + 2,
+        w: rect.w,
+        h: rect.h - 2,
+    }
+}
+
 fn draw_teammate_selection(platform: &Platform,
                            state: &mut State,
                            rect: SpecRect,
@@ -1031,7 +3590,7 @@ fn draw_teammate_selection(platform: &Platform,
             y: rect.y,
             w: button_width,
             h: rect.h,
-            text: "TeammateOne".to_string(),
+            text: teammate_name(state, TeammateOne),
             id: 123,
         };
 
@@ -1040,7 +3599,7 @@ fn draw_teammate_selection(platform: &Platform,
                      &spec_one,
                      left_mouse_pressed,
                      left_mouse_released) {
-            state.current_player = Some(TeammatePlayer(TeammateOne))
+            set_current_player(state, Some(TeammatePlayer(TeammateOne)))
         }
 
         let spec_two = ButtonSpec {
@@ -1048,7 +3607,7 @@ fn draw_teammate_selection(platform: &Platform,
             y: rect.y,
             w: button_width,
             h: rect.h,
-            text: "TeammateTwo".to_string(),
+            text: teammate_name(state, TeammateTwo),
             id: 234,
         };
 
@@ -1057,12 +3616,13 @@ fn draw_teammate_selection(platform: &Platform,
                      &spec_two,
                      left_mouse_pressed,
                      left_mouse_released) {
-            state.current_player = Some(TeammatePlayer(TeammateTwo))
+            set_current_player(state, Some(TeammatePlayer(TeammateTwo)))
         }
     } else {
         //no choice so need for buttons
-        state.current_player = or_available_opponent(state,
-                                                     filtered_teammates.get(0).cloned().cloned());
+        let next_player = or_available_opponent(state,
+                                                filtered_teammates.first().cloned().cloned());
+        set_current_player(state, next_player);
     }
 
 }
@@ -1116,14 +3676,18 @@ fn draw_subsuit_menu(platform: &Platform,
                 text: subsuit.to_string(),
                 id: 1123 + index,
             };
+            let shortcut = 1 + i as u8;
 
             if do_button(platform,
                          &mut state.ui_context,
                          &spec,
                          left_mouse_pressed,
-                         left_mouse_released) {
+                         left_mouse_released) ||
+               shortcut_pressed(&state.ui_context, shortcut) {
                 action(state, subsuit);
             }
+
+            draw_shortcut_hint(platform, &spec, shortcut);
         }
     }
 
@@ -1143,14 +3707,18 @@ fn draw_subsuit_menu(platform: &Platform,
                 text: subsuit.to_string(),
                 id: 2234 + index,
             };
+            let shortcut = 5 + i as u8;
 
             if do_button(platform,
                          &mut state.ui_context,
                          &spec,
                          left_mouse_pressed,
-                         left_mouse_released) {
+                         left_mouse_released) ||
+               shortcut_pressed(&state.ui_context, shortcut) {
                 action(state, subsuit);
             }
+
+            draw_shortcut_hint(platform, &spec, shortcut);
         }
     }
 }
@@ -1205,10 +3773,10 @@ fn draw_ask_suit_menu(platform: &Platform,
                          left_mouse_pressed,
                          left_mouse_released) {
 
-                state.menu_state = if let Some(TeammatePlayer(teammate)) = state.current_player {
-                    AskStep4(ToOpponent(teammate, opponent), suit, value)
+                if let Some(TeammatePlayer(teammate)) = state.current_player {
+                    start_ask(state, ToOpponent(teammate, opponent), suit, value);
                 } else {
-                    Main
+                    state.menu_state = Main;
                 }
             }
         }
@@ -1229,10 +3797,14 @@ fn draw_ask_result(platform: &Platform,
 
     let (target_has_card, target_name, target_is_opponent) = match ask_vector {
         ToTeammate(_, target) => {
-            (has_card(teammate_hand(state, target), suit, value), teammate_name(target), false)
+            (has_card(teammate_hand(state, target), suit, value),
+             teammate_name(state, target),
+             false)
         }
         ToOpponent(_, target) => {
-            (has_card(opponent_hand(state, target), suit, value), opponent_name(target), true)
+            (has_card(opponent_hand(state, target), suit, value),
+             opponent_name(state, target),
+             true)
         }
     };
 
@@ -1240,11 +3812,12 @@ fn draw_ask_result(platform: &Platform,
 
     print_horizontally_centered_line(platform, &rect, question, rect.y + MENU_OFFSET);
 
-    if target_has_card {
-        print_centered_line(platform, &rect, "\"Yes, I do. Here you go.\"");
-    } else {
-        print_centered_line(platform, &rect, "\"Nope! Now it's my turn!\"");
-    }
+    let answer = match state.dialogue {
+        Some(ref line) => line.clone(),
+        None if target_has_card => HAND_OVER_LINES[0].1.to_string(),
+        None => REFUSE_LINES[0].1.to_string(),
+    };
+    print_centered_line(platform, &rect, &format!("\"{}\"", answer));
 
     let spec = ButtonSpec {
         x: rect.x + MENU_OFFSET + (button_width + MENU_OFFSET),
@@ -1259,70 +3832,216 @@ fn draw_ask_result(platform: &Platform,
         id: 4456,
     };
 
+    if let Some(delay) = autoplay_delay(state, ask_vector) {
+        let started = *state.autoplay_started.get_or_insert_with(Instant::now);
+
+        let hint = if state.autoplay_paused {
+            "Paused, press P to continue"
+        } else if started.elapsed() >= delay {
+            resolve_ask(state, ask_vector, suit, value);
+            return;
+        } else {
+            "Press P to pause"
+        };
+
+        print_horizontally_centered_line(platform, &rect, hint, spec.y - 2);
+    }
+
     if do_button(platform,
                  &mut state.ui_context,
                  &spec,
                  left_mouse_pressed,
                  left_mouse_released) {
-        if target_has_card {
-            let taken_card = {
-                let mut index = 0;
-                let target_hand = match ask_vector {
-                    ToTeammate(_, target) => teammate_hand_mut(state, target),
-                    ToOpponent(_, target) => opponent_hand_mut(state, target),
-                };
-                for card in target_hand.iter() {
-                    if card.suit == suit && card.value == value {
-                        break;
-                    }
+        resolve_ask(state, ask_vector, suit, value);
+    }
 
-                    index += 1;
-                }
+}
 
-                if index < target_hand.len() {
-                    Some(target_hand.swap_remove(index))
-                } else {
-                    None
-                }
-            };
+//how long the result of this ask stays up before it goes by on its own,
+//None if it waits for a click
+fn autoplay_delay(state: &State, ask_vector: AskVector) -> Option<Duration> {
+    let involves_team = match ask_vector {
+        //the player's own asks are not CPU turns, unless the CPU is making them
+        ToOpponent(ThePlayer, _) if !state.autopilot => return None,
+        ToOpponent(_, _) => true,
+        ToTeammate(_, target) => target == ThePlayer,
+    };
+    if involves_team && state.settings.acknowledge_team_asks && !state.spectating {
+        return None;
+    }
 
-            if let Some(card) = taken_card {
-                {
-                    let asker_hand = match ask_vector {
-                        ToTeammate(source, _) => opponent_hand_mut(state, source),
-                        ToOpponent(source, _) => teammate_hand_mut(state, source),
-                    };
-                    if let Err(insertion_index) = asker_hand.binary_search(&card) {
-                        asker_hand.insert(insertion_index, card);
-                    }
-                }
+    match state.settings.autoplay {
+        AutoplaySpeed::Instant => Some(Duration::from_millis(0)),
+        AutoplaySpeed::Fast => Some(Duration::from_millis(500)),
+        AutoplaySpeed::Normal => Some(Duration::from_millis(1500)),
+        AutoplaySpeed::StepByStep => None,
+    }
+}
+
+fn start_ask(state: &mut State, ask_vector: AskVector, suit: Suit, value: Value) {
+    let (_, target) = ask_vector_players(ask_vector);
+
+    let lines = if has_card(player_hand(state, target), suit, value) {
+        &HAND_OVER_LINES
+    } else {
+        &REFUSE_LINES
+    };
+    state.dialogue = pick_line(state, target, lines);
+
+    state.menu_state = AskStep4(ask_vector, suit, value);
+}
 
-                note_successful_ask(state, ask_vector, suit, value);
+fn start_declaration(state: &mut State, declaration: Declaration) {
+    if let DeclareStep3(info) = declaration {
+        let (declarer, subsuit, claimed) = match info {
+            TeammateDInfo(declarer, subsuit, teammates) => {
+                (TeammatePlayer(declarer), subsuit, teammates_to_players(teammates))
+            }
+            OpponentDInfo(declarer, subsuit, opponents) => {
+                (OpponentPlayer(declarer), subsuit, opponents_to_players(opponents))
             }
+        };
+
+        let correct = pairs_from_subsuit(subsuit)
+            .iter()
+            .zip(claimed.iter())
+            .all(|(&(suit, value), &player)| has_card(player_hand(state, player), suit, value));
 
+        let lines = if correct {
+            &DECLARED_CORRECTLY_LINES
         } else {
-            state.current_player = Some(match ask_vector {
-                                            ToTeammate(_, target) => TeammatePlayer(target),
-                                            ToOpponent(_, target) => OpponentPlayer(target),
-                                        });
+            &DECLARED_WRONGLY_LINES
+        };
+        state.dialogue = pick_line(state, declarer, lines);
+    }
+
+    state.declaration = Some(declaration);
+}
+
+//picks one of the lines for the seat's personality, so the same game always
+//gets the same lines
+fn pick_line(state: &mut State,
+             player: Player,
+             lines: &[(Personality, &str)])
+             -> Option<String> {
+    //we don't put words in the player's mouth
+    if player == TeammatePlayer(ThePlayer) {
+        return None;
+    }
+
+    let personality = state.personalities.get(&player).cloned().unwrap_or(Plain);
+
+    let choices: Vec<&str> = lines
+        .iter()
+        .filter(|&&(p, _)| p == personality)
+        .map(|&(_, line)| line)
+        .collect();
+
+    state.rng.choose(&choices).map(|line| line.to_string())
+}
+
+const HAND_OVER_LINES: [(Personality, &str); 10] =
+    [(Plain, "Yes, I do. Here you go."),
+     (Cheerful, "I sure do! Take good care of it!"),
+     (Cheerful, "Yep! Happy to help!"),
+     (Cheerful, "Good guess! It's all yours."),
+     (Grumpy, "Fine. Take it."),
+     (Grumpy, "Ugh. Yes."),
+     (Grumpy, "I was going to use that, you know."),
+     (Smug, "Enjoy it while it lasts."),
+     (Smug, "Take it. I'll have it back soon enough."),
+     (Smug, "Lucky guess.")];
+
+const REFUSE_LINES: [(Personality, &str); 10] =
+    [(Plain, "Nope! Now it's my turn!"),
+     (Cheerful, "Sorry, not me! My turn now!"),
+     (Cheerful, "Nope! But thanks for asking!"),
+     (Cheerful, "Not here! Ooh, my turn!"),
+     (Grumpy, "No. And now it's my turn."),
+     (Grumpy, "Do I look like I have it?"),
+     (Grumpy, "No. Stop bothering me."),
+     (Smug, "Not even close. My turn."),
+     (Smug, "Ha! No."),
+     (Smug, "Did you really think I'd have it?")];
+
+const DECLARED_CORRECTLY_LINES: [(Personality, &str); 10] =
+    [(Plain, "That's the lot."),
+     (Cheerful, "Yay, we did it!"),
+     (Cheerful, "Teamwork!"),
+     (Cheerful, "I knew it!"),
+     (Grumpy, "Finally."),
+     (Grumpy, "About time."),
+     (Grumpy, "That's one down."),
+     (Smug, "Obviously."),
+     (Smug, "Was there ever any doubt?"),
+     (Smug, "Too easy.")];
+
+const DECLARED_WRONGLY_LINES: [(Personality, &str); 10] =
+    [(Plain, "Oops."),
+     (Cheerful, "Whoops! Oh well!"),
+     (Cheerful, "Aw, I really thought I had that one!"),
+     (Cheerful, "Well, that was fun anyway!"),
+     (Grumpy, "This game is rigged."),
+     (Grumpy, "Who shuffled these?"),
+     (Grumpy, "Don't say anything."),
+     (Smug, "I meant to do that."),
+     (Smug, "That was a test. You all passed."),
+     (Smug, "A minor setback.")];
+
+fn resolve_ask(state: &mut State, ask_vector: AskVector, suit: Suit, value: Value) {
+    state.dialogue = None;
+    state.autoplay_started = None;
+    let target_has_card = match ask_vector {
+        ToTeammate(_, target) => has_card(teammate_hand(state, target), suit, value),
+        ToOpponent(_, target) => has_card(opponent_hand(state, target), suit, value),
+    };
+
+    state.history.push(Asked(ask_vector, suit, value, target_has_card));
+
+    if target_has_card {
+        let (source, target) = ask_vector_players(ask_vector);
+
+        let taken_card = remove_from_hand(player_hand_mut(state, target), suit, value);
+
+        if let Some(card) = taken_card {
+            add_to_hand(state, source, card);
 
-            note_unsuccessful_ask(state, ask_vector, suit, value);
+            note_successful_ask(state, ask_vector, suit, value);
         }
 
-        state.menu_state = Main;
+    } else {
+        set_current_player(state,
+                           Some(match ask_vector {
+                                    ToTeammate(_, target) => TeammatePlayer(target),
+                                    ToOpponent(_, target) => OpponentPlayer(target),
+                                }));
+
+        note_unsuccessful_ask(state, ask_vector, suit, value);
+    }
+
+    state.menu_state = Main;
+}
+
+fn set_current_player(state: &mut State, player: Option<Player>) {
+    if player != state.current_player {
+        if let Some(p) = player {
+            state.history.push(TurnPassed(p));
+        }
     }
 
+    state.current_player = player;
 }
 
 //everyone now knows that `source` has this card and the target has one fewer
 fn note_successful_ask(state: &mut State, ask_vector: AskVector, suit: Suit, value: Value) {
-    let (source, target) = match ask_vector {
-        ToTeammate(source, target) => (OpponentPlayer(source), TeammatePlayer(target)),
-        ToOpponent(source, target) => (TeammatePlayer(source), OpponentPlayer(target)),
-    };
+    let (source, target) = ask_vector_players(ask_vector);
+    let subsuit = subsuit_from_pair(suit, value);
 
     for memory in get_memories(state).iter_mut() {
         if let Some(target_knowledge) = memory.get_mut(&target) {
+            //they may have just given away their last card of the half-suit
+            forget_has_some_of(target_knowledge, subsuit);
+
             let target_hand = &mut target_knowledge.model_hand;
 
             let mut card_was_not_found = true;
@@ -1346,6 +4065,8 @@ fn note_successful_ask(state: &mut State, ask_vector: AskVector, suit: Suit, val
             }
         }
         if let Some(source_knowledge) = memory.get_mut(&source) {
+            note_has_some_of(source_knowledge, subsuit);
+
             let ref mut source_hand = source_knowledge.model_hand;
 
             source_hand.push(Known(suit, value));
@@ -1359,10 +4080,7 @@ fn note_successful_ask(state: &mut State, ask_vector: AskVector, suit: Suit, val
 
 //everyone now knows that neither `source` or `target` have this card
 fn note_unsuccessful_ask(state: &mut State, ask_vector: AskVector, suit: Suit, value: Value) {
-    let (source, target) = match ask_vector {
-        ToTeammate(source, target) => (OpponentPlayer(source), TeammatePlayer(target)),
-        ToOpponent(source, target) => (TeammatePlayer(source), OpponentPlayer(target)),
-    };
+    let (source, target) = ask_vector_players(ask_vector);
 
     for memory in get_memories(state).iter_mut() {
         if let Some(knowledge) = memory.get_mut(&target) {
@@ -1370,6 +4088,7 @@ fn note_unsuccessful_ask(state: &mut State, ask_vector: AskVector, suit: Suit, v
         }
         if let Some(knowledge) = memory.get_mut(&source) {
             note_does_not_have(knowledge, suit, value);
+            note_has_some_of(knowledge, subsuit_from_pair(suit, value));
         }
     }
 
@@ -1393,6 +4112,7 @@ fn infer(state: &mut State) {
 
                             eliminated_players.push(player);
                         }
+                        KnownToHaveSomeOf(_) => {}
                     }
                 }
             }
@@ -1443,50 +4163,50 @@ fn get_other_player(players: &Vec<Player>) -> Player {
 
 fn set_any_declarations(state: &mut State) {
     let mut cpu_players = cpu_players();
+    if state.autopilot {
+        cpu_players.push(TeammatePlayer(ThePlayer));
+    }
 
     state.rng.shuffle(&mut cpu_players);
 
     for &player in cpu_players.iter() {
-        let new_declaration = get_new_delcaration(state, player);
-
-        if new_declaration.is_some() {
-            state.declaration = new_declaration;
+        if let Some(declaration) = get_new_delcaration(state, player) {
+            start_declaration(state, declaration);
             return;
         }
     }
 }
 
-fn get_new_delcaration(state: &mut State, player: Player) -> Option<Declaration> {
-    if let Some(memory) = get_memory_mut(state, player) {
-        let same_team_players = get_same_team_players(player);
+fn get_new_delcaration(state: &State, player: Player) -> Option<Declaration> {
+    let memory = get_memory(state, player);
+    let same_team_players = get_same_team_players(player);
 
-        'subsuits: for &subsuit in SubSuit::all_values().iter() {
-            let mut known_owners = Vec::new();
+    'subsuits: for &subsuit in SubSuit::all_values().iter() {
+        let mut known_owners = Vec::new();
 
-            for (suit, value) in pairs_from_subsuit(subsuit) {
-                match (player, known_owning_player(memory, &same_team_players, suit, value)) {
-                    (TeammatePlayer(_), Some(TeammatePlayer(owner))) => {
-                        known_owners.push(TeammatePlayer(owner))
-                    }
-                    (OpponentPlayer(_), Some(OpponentPlayer(owner))) => {
-                        known_owners.push(OpponentPlayer(owner))
-                    }
-                    _ => {
-                        continue 'subsuits;
-                    }
+        for (suit, value) in pairs_from_subsuit(subsuit) {
+            match (player, known_owning_player(memory, &same_team_players, suit, value)) {
+                (TeammatePlayer(_), Some(TeammatePlayer(owner))) => {
+                    known_owners.push(TeammatePlayer(owner))
+                }
+                (OpponentPlayer(_), Some(OpponentPlayer(owner))) => {
+                    known_owners.push(OpponentPlayer(owner))
+                }
+                _ => {
+                    continue 'subsuits;
                 }
             }
+        }
 
-            match player {
-                TeammatePlayer(t) => {
-                    if let Some(teammates) = get_teammate_declaration_array(known_owners) {
-                        return Some(DeclareStep3(TeammateDInfo(t, subsuit, teammates)));
-                    }
+        match player {
+            TeammatePlayer(t) => {
+                if let Some(teammates) = get_teammate_declaration_array(known_owners) {
+                    return Some(DeclareStep3(TeammateDInfo(t, subsuit, teammates)));
                 }
-                OpponentPlayer(o) => {
-                    if let Some(opponents) = get_opponent_declaration_array(known_owners) {
-                        return Some(DeclareStep3(OpponentDInfo(o, subsuit, opponents)));
-                    }
+            }
+            OpponentPlayer(o) => {
+                if let Some(opponents) = get_opponent_declaration_array(known_owners) {
+                    return Some(DeclareStep3(OpponentDInfo(o, subsuit, opponents)));
                 }
             }
         }
@@ -1582,33 +4302,57 @@ fn note_does_not_have(knowledge: &mut Knowledge, suit: Suit, value: Value) {
     }
 }
 
+fn note_has_some_of(knowledge: &mut Knowledge, subsuit: SubSuit) {
+    let already_known = knowledge
+        .facts
+        .iter()
+        .any(|&fact| match fact {
+                 KnownToHaveSomeOf(known_subsuit) => known_subsuit == subsuit,
+                 _ => false,
+             });
+
+    if !already_known {
+        knowledge.facts.push(KnownToHaveSomeOf(subsuit));
+    }
+}
+
+fn forget_has_some_of(knowledge: &mut Knowledge, subsuit: SubSuit) {
+    knowledge
+        .facts
+        .retain(|&fact| match fact {
+                    KnownToHaveSomeOf(known_subsuit) => known_subsuit != subsuit,
+                    _ => true,
+                });
+}
+
 fn get_memories(state: &mut State) -> Vec<&mut Memory> {
-    vec![&mut state.teammate_1_memory,
+    vec![&mut state.player_memory,
+         &mut state.teammate_1_memory,
          &mut state.teammate_2_memory,
          &mut state.opponent_1_memory,
          &mut state.opponent_2_memory,
          &mut state.opponent_3_memory]
 }
 
-fn get_memory_mut(state: &mut State, player: Player) -> Option<&mut Memory> {
+fn get_memory_mut(state: &mut State, player: Player) -> &mut Memory {
     match player {
-        TeammatePlayer(ThePlayer) => None,
-        TeammatePlayer(TeammateOne) => Some(&mut state.teammate_1_memory),
-        TeammatePlayer(TeammateTwo) => Some(&mut state.teammate_2_memory),
-        OpponentPlayer(OpponentZero) => Some(&mut state.opponent_1_memory),
-        OpponentPlayer(OpponentOne) => Some(&mut state.opponent_2_memory),
-        OpponentPlayer(OpponentTwo) => Some(&mut state.opponent_3_memory),
+        TeammatePlayer(ThePlayer) => &mut state.player_memory,
+        TeammatePlayer(TeammateOne) => &mut state.teammate_1_memory,
+        TeammatePlayer(TeammateTwo) => &mut state.teammate_2_memory,
+        OpponentPlayer(OpponentZero) => &mut state.opponent_1_memory,
+        OpponentPlayer(OpponentOne) => &mut state.opponent_2_memory,
+        OpponentPlayer(OpponentTwo) => &mut state.opponent_3_memory,
     }
 }
 
-fn get_memory(state: &State, player: Player) -> Option<&Memory> {
+fn get_memory(state: &State, player: Player) -> &Memory {
     match player {
-        TeammatePlayer(ThePlayer) => None,
-        TeammatePlayer(TeammateOne) => Some(&state.teammate_1_memory),
-        TeammatePlayer(TeammateTwo) => Some(&state.teammate_2_memory),
-        OpponentPlayer(OpponentZero) => Some(&state.opponent_1_memory),
-        OpponentPlayer(OpponentOne) => Some(&state.opponent_2_memory),
-        OpponentPlayer(OpponentTwo) => Some(&state.opponent_3_memory),
+        TeammatePlayer(ThePlayer) => &state.player_memory,
+        TeammatePlayer(TeammateOne) => &state.teammate_1_memory,
+        TeammatePlayer(TeammateTwo) => &state.teammate_2_memory,
+        OpponentPlayer(OpponentZero) => &state.opponent_1_memory,
+        OpponentPlayer(OpponentOne) => &state.opponent_2_memory,
+        OpponentPlayer(OpponentTwo) => &state.opponent_3_memory,
     }
 }
 
@@ -1627,15 +4371,16 @@ fn get_same_team_players(player: Player) -> Vec<Player> {
     }
 }
 
-fn teammate_name(teammate: Teammate) -> String {
-    match teammate {
-        ThePlayer => "Player".to_string(),
-        _ => teammate.to_string(),
-    }
+fn player_name(state: &State, player: Player) -> String {
+    state.names.get(&player).cloned().unwrap_or_else(|| player.to_string())
+}
+
+fn teammate_name(state: &State, teammate: Teammate) -> String {
+    player_name(state, TeammatePlayer(teammate))
 }
 
-fn opponent_name(opponent: Opponent) -> String {
-    opponent.to_string()
+fn opponent_name(state: &State, opponent: Opponent) -> String {
+    player_name(state, OpponentPlayer(opponent))
 }
 
 macro_rules! array_update {
@@ -1657,12 +4402,12 @@ fn draw_declare_radio_buttons(platform: &Platform,
                               teammates: [Teammate; 6]) {
     let column_width = (rect.w / 5) - (MENU_OFFSET as f64 / 5.0).round() as i32;
 
-    let labels = ["Player", "TeammateOne", "TeammateTwo"];
-    for i in 0..labels.len() {
-        let label = labels[i];
-        (platform.print_xy)(rect.x + ((i + 1) as i32 * column_width) - (label.len() as i32 / 2),
+    for (i, &teammate) in Teammate::all_values().iter().enumerate() {
+        let label = teammate_name(state, teammate);
+        (platform.print_xy)(rect.x + ((i + 1) as i32 * column_width) -
+                            (label.chars().count() as i32 / 2),
                             rect.y,
-                            label);
+                            &label);
 
     }
 
@@ -1736,7 +4481,7 @@ fn draw_declare_radio_buttons(platform: &Platform,
                  &spec,
                  left_mouse_pressed,
                  left_mouse_released) {
-        state.declaration = Some(DeclareStep3(TeammateDInfo(ThePlayer, subsuit, teammates)));
+        start_declaration(state, DeclareStep3(TeammateDInfo(ThePlayer, subsuit, teammates)));
     }
 }
 
@@ -1746,72 +4491,76 @@ fn draw_declare_result(platform: &Platform,
                        left_mouse_pressed: bool,
                        left_mouse_released: bool,
                        info: DeclarationInfo) {
-    let row_width = (rect.w / 6) - (MENU_OFFSET as f64 / 6.0).round() as i32;
+    //leave room for the button at the bottom
+    let row_height = (rect.h - rect.h / 5) / 6;
     let subsuit = match info {
         TeammateDInfo(_, subsuit, _) => subsuit,
         OpponentDInfo(_, subsuit, _) => subsuit,
     };
 
+    let (declarer, claimed) = match info {
+        TeammateDInfo(declarer, _, teammates) => {
+            (TeammatePlayer(declarer), teammates_to_players(teammates))
+        }
+        OpponentDInfo(declarer, _, opponents) => {
+            (OpponentPlayer(declarer), opponents_to_players(opponents))
+        }
+    };
+
     let pairs = pairs_from_subsuit(subsuit);
     for i in 0..6 {
         let (suit, value) = pairs[i];
+        let claimed_holder = claimed[i];
 
-        let y = rect.y + (i as i32 * row_width) / 6;
-
-        match info {
-            TeammateDInfo(declarer, _, teammates) => {
-                let teammate = teammates[i];
-
-                print_horizontally_centered_line(platform,
-                                                 &rect,
-                                                 &format!("{} said that {} had the {} of {}",
-                                                         declarer.to_string(),
-                                                         teammate,
-                                                         value,
-                                                         suit),
-                                                 y);
-
-                let result_str = if has_card(teammate_hand(state, teammate), suit, value) {
-                    match teammate {
-                        ThePlayer => "And you did have it.",
-                        _ => "And they did have it!",
-                    }
-                } else {
-                    match teammate {
-                        ThePlayer => "But you didn't have it?! Nice move, genius.",
-                        //TODO say who did have it here
-                        _ => "But they didn't have it!",
-                    }
-                };
+        let y = rect.y + i as i32 * row_height;
 
-                print_horizontally_centered_line(platform, &rect, result_str, y + 1);
-            }
-            OpponentDInfo(declarer, _, opponents) => {
-                let opponent = opponents[i];
+        let holder = card_holder(state, suit, value);
 
-                print_horizontally_centered_line(platform,
-                                                 &rect,
-                                                 &format!("{} said that {} had the {} of {}",
-                                                         declarer.to_string(),
-                                                         opponent,
-                                                         value,
-                                                         suit),
-                                                 y);
+        let result_str = if holder == Some(claimed_holder) {
+            match claimed_holder {
+                TeammatePlayer(ThePlayer) => "And you did have it.".to_string(),
+                _ => "And they did have it!".to_string(),
+            }
+        } else {
+            let holder_str = match holder {
+                Some(holder) => player_name(state, holder),
+                None => "nobody".to_string(),
+            };
 
-                let result_str = if has_card(opponent_hand(state, opponent), suit, value) {
+            match claimed_holder {
+                TeammatePlayer(ThePlayer) => {
+                    format!("But you didn't have it?! {} did. Nice move, genius.",
+                            holder_str)
+                }
+                _ => format!("But they didn't have it! {} did.", holder_str),
+            }
+        };
 
-                    "And they did have it!"
+        //the lines that made the declaration fail stand out
+        let foreground = (platform.get_foreground)();
+        if holder != Some(claimed_holder) {
+            (platform.set_foreground)(WRONG_COLOR);
+        }
 
-                } else {
-                    //TODO say who did have it here
-                    "But they didn't have it!"
-                };
+        print_horizontally_centered_line(platform,
+                                         &rect,
+                                         &format!("{} said that {} had the {} of {}",
+                                                 player_name(state, declarer),
+                                                 player_name(state, claimed_holder),
+                                                 value,
+                                                 suit),
+                                         y);
 
-                print_horizontally_centered_line(platform, &rect, result_str, y + 1);
-            }
-        }
+        print_horizontally_centered_line(platform, &rect, &result_str, y + 1);
 
+        (platform.set_foreground)(foreground);
+    }
 
+    if let Some(ref line) = state.dialogue {
+        print_horizontally_centered_line(platform,
+                                         &rect,
+                                         &format!("{}: \"{}\"", player_name(state, declarer), line),
+                                         rect.y + 6 * row_height);
     }
 
     let button_width = (rect.w / 3) - (MENU_OFFSET as f64 / 3.0).round() as i32;
@@ -1830,60 +4579,108 @@ fn draw_declare_result(platform: &Platform,
                  &spec,
                  left_mouse_pressed,
                  left_mouse_released) {
-        let mut all_correct = true;
-        let mut removed_cards = Vec::new();
-        for i in 0..6 {
-            let (suit, value) = pairs[i];
+        resolve_declaration(state, info);
+    }
+}
 
+fn resolve_declaration(state: &mut State, info: DeclarationInfo) {
+    state.dialogue = None;
+    let (declarer, subsuit, claimed) = match info {
+        TeammateDInfo(declarer, subsuit, teammates) => {
+            (TeammatePlayer(declarer), subsuit, teammates_to_players(teammates))
+        }
+        OpponentDInfo(declarer, subsuit, opponents) => {
+            (OpponentPlayer(declarer), subsuit, opponents_to_players(opponents))
+        }
+    };
+    let pairs = pairs_from_subsuit(subsuit);
 
-            let card_not_found = {
-                let (hand, player) = match info {
-                    TeammateDInfo(_, _, teammates) => {
-                        (teammate_hand_mut(state, teammates[i]), TeammatePlayer(teammates[i]))
-                    }
-                    OpponentDInfo(_, _, opponents) => {
-                        (opponent_hand_mut(state, opponents[i]), OpponentPlayer(opponents[i]))
-                    }
-                };
+    let mut all_correct = true;
+    let mut removed_cards = Vec::new();
+    let mut actual = claimed;
+    for i in 0..6 {
+        let (suit, value) = pairs[i];
 
-                if let Some(card) = remove_from_hand(hand, suit, value) {
-                    removed_cards.push((card.suit, card.value, player));
 
-                    false
-                } else {
-                    true
+        let card_not_found = {
+            let (hand, player) = match info {
+                TeammateDInfo(_, _, teammates) => {
+                    (teammate_hand_mut(state, teammates[i]), TeammatePlayer(teammates[i]))
+                }
+                OpponentDInfo(_, _, opponents) => {
+                    (opponent_hand_mut(state, opponents[i]), OpponentPlayer(opponents[i]))
                 }
             };
 
-            if card_not_found {
-                all_correct = false;
-                for &mut (ref mut hand, player) in all_hands_mut(state).iter_mut() {
-                    if let Some(card) = remove_from_hand(hand, suit, value) {
-                        removed_cards.push((card.suit, card.value, player));
+            if let Some(card) = remove_from_hand(hand, suit, value) {
+                removed_cards.push((card.suit, card.value, player));
 
-                        break;
-                    }
-                }
+                false
+            } else {
+                true
             }
-        }
+        };
 
-        match (info, all_correct) {
-            (TeammateDInfo(_, _, _), true) |
-            (OpponentDInfo(_, _, _), false) => {
-                state.player_points += 1;
-            }
-            (TeammateDInfo(_, _, _), false) |
-            (OpponentDInfo(_, _, _), true) => {
-                state.opponent_points += 1;
+        if card_not_found {
+            all_correct = false;
+            for &mut (ref mut hand, player) in all_hands_mut(state).iter_mut() {
+                if let Some(card) = remove_from_hand(hand, suit, value) {
+                    removed_cards.push((card.suit, card.value, player));
+                    actual[i] = player;
+
+                    break;
+                }
             }
         }
+    }
+
+    let same_team_players = get_same_team_players(declarer);
+    let cancelled = !all_correct && state.settings.rules == MisassignedCancelled &&
+                    actual.iter().all(|p| same_team_players.contains(p));
+
+    let record = DeclarationRecord {
+        declarer,
+        subsuit,
+        claimed,
+        actual,
+        correct: all_correct,
+        cancelled,
+    };
+
+    if record.cancelled {
+        //nobody scores
+    } else if record.scored_for_player_team() {
+        state.player_points += 1;
+    } else {
+        state.opponent_points += 1;
+    }
+
+    state.history.push(Declared(record));
+
+    update_memories_after_suit_declared(state, removed_cards);
+
+    state.declaration = None;
+    state.suits_in_play_bits &= !u8::from(subsuit);
+}
+
+fn teammates_to_players(teammates: [Teammate; 6]) -> [Player; 6] {
+    let mut result = [TeammatePlayer(ThePlayer); 6];
+
+    for i in 0..6 {
+        result[i] = TeammatePlayer(teammates[i]);
+    }
 
-        update_memories_after_suit_declared(state, removed_cards);
+    result
+}
 
-        state.declaration = None;
-        state.suits_in_play_bits &= !u8::from(subsuit);
+fn opponents_to_players(opponents: [Opponent; 6]) -> [Player; 6] {
+    let mut result = [OpponentPlayer(OpponentZero); 6];
 
+    for i in 0..6 {
+        result[i] = OpponentPlayer(opponents[i]);
     }
+
+    result
 }
 
 fn update_memories_after_suit_declared(state: &mut State,
@@ -1922,6 +4719,13 @@ fn update_memories_after_suit_declared(state: &mut State,
     }
 }
 
+fn card_holder(state: &State, suit: Suit, value: Value) -> Option<Player> {
+    ALL_SEATS
+        .iter()
+        .find(|&&player| has_card(player_hand(state, player), suit, value))
+        .cloned()
+}
+
 fn all_hands_mut(state: &mut State) -> Vec<(&mut Hand, Player)> {
     vec![(&mut state.player, TeammatePlayer(ThePlayer)),
          (&mut state.teammate_1, TeammatePlayer(TeammateOne)),
@@ -1932,11 +4736,16 @@ fn all_hands_mut(state: &mut State) -> Vec<(&mut Hand, Player)> {
 }
 
 fn cpu_hands(state: &State) -> Vec<(&Hand, Player)> {
-    vec![(&state.teammate_1, TeammatePlayer(TeammateOne)),
-         (&state.teammate_2, TeammatePlayer(TeammateTwo)),
-         (&state.opponent_1, OpponentPlayer(OpponentZero)),
-         (&state.opponent_2, OpponentPlayer(OpponentOne)),
-         (&state.opponent_3, OpponentPlayer(OpponentTwo))]
+    let mut hands = vec![(&state.teammate_1, TeammatePlayer(TeammateOne)),
+                         (&state.teammate_2, TeammatePlayer(TeammateTwo)),
+                         (&state.opponent_1, OpponentPlayer(OpponentZero)),
+                         (&state.opponent_2, OpponentPlayer(OpponentOne)),
+                         (&state.opponent_3, OpponentPlayer(OpponentTwo))];
+    if state.autopilot {
+        hands.push((&state.player, TeammatePlayer(ThePlayer)));
+    }
+
+    hands
 }
 
 fn remove_from_hand(hand: &mut Hand, suit: Suit, value: Value) -> Option<Card> {
@@ -2007,12 +4816,177 @@ fn do_radio_button(platform: &Platform,
         context.set_next_hot(id);
     }
 
+    context.set_focusable(id);
+    if context.is_focused(id) {
+        if context.keyboard.activate {
+            result = true;
+        }
+
+        (platform.print_xy)(x - 1, y, "[");
+        (platform.print_xy)(x + 1, y, "]");
+    }
+
     (platform.print_xy)(x, y, if checked { "☑" } else { "☐" });
 
     result
 }
 
 
+const HIGHLIGHT_COLOR: Color = Color {
+    red: 255,
+    green: 255,
+    blue: 0,
+    alpha: 255,
+};
+
+const TEAMMATE_COLOR: Color = Color {
+    red: 96,
+    green: 192,
+    blue: 255,
+    alpha: 255,
+};
+
+const OPPONENT_COLOR: Color = Color {
+    red: 255,
+    green: 160,
+    blue: 64,
+    alpha: 255,
+};
+
+const WRONG_COLOR: Color = Color {
+    red: 255,
+    green: 64,
+    blue: 64,
+    alpha: 255,
+};
+
+//a single line of clickable text, highlighted instead of boxed
+fn do_text_button(platform: &Platform,
+                  context: &mut UIContext,
+                  (x, y): (i32, i32),
+                  text: &str,
+                  id: UiId,
+                  left_mouse_pressed: bool,
+                  left_mouse_released: bool)
+                  -> bool {
+    let mut result = false;
+
+    let mouse_pos = (platform.mouse_position)();
+    let inside = inside_rect(mouse_pos, x, y, text.chars().count() as i32, 1);
+
+    if context.active == id {
+        if left_mouse_released {
+            result = context.hot == id && inside;
+
+            context.set_not_active();
+        }
+    } else if context.hot == id && left_mouse_pressed {
+        context.set_active(id);
+    }
+
+    if inside {
+        context.set_next_hot(id);
+    }
+
+    context.set_focusable(id);
+    if context.is_focused(id) && context.keyboard.activate {
+        result = true;
+    }
+
+    if context.hot == id || context.is_focused(id) {
+        let foreground = (platform.get_foreground)();
+        (platform.set_foreground)(HIGHLIGHT_COLOR);
+        (platform.print_xy)(x, y, text);
+        (platform.set_foreground)(foreground);
+    } else {
+        (platform.print_xy)(x, y, text);
+    }
+
+    result
+}
+
+//a single line of editable text. Returns true if the text changed.
+fn do_text_field(platform: &Platform,
+                 context: &mut UIContext,
+                 rect: &SpecRect,
+                 text: &mut String,
+                 id: UiId,
+                 left_mouse_pressed: bool,
+                 left_mouse_released: bool)
+                 -> bool {
+    let mut result = false;
+
+    let mouse_pos = (platform.mouse_position)();
+    let inside = inside_rect(mouse_pos, rect.x, rect.y, rect.w, 1);
+
+    if context.active == id {
+        if left_mouse_released {
+            if context.hot == id && inside {
+                context.focused = id;
+            }
+
+            context.set_not_active();
+        }
+    } else if context.hot == id && left_mouse_pressed {
+        context.set_active(id);
+    }
+
+    if inside {
+        context.set_next_hot(id);
+    }
+
+    context.set_focusable(id);
+    let focused = context.is_focused(id);
+
+    //while focused the field shows what has been typed, even if the caller
+    //can't make sense of it yet
+    if focused {
+        let is_editing = match context.editing {
+            Some((editing_id, _)) => editing_id == id,
+            None => false,
+        };
+        if !is_editing {
+            context.editing = Some((id, text.clone()));
+        }
+    }
+
+    let typed = context.keyboard.typed;
+    let back = context.keyboard.back;
+    let shown_text = match context.editing {
+        Some((editing_id, ref mut buffer)) if focused && editing_id == id => {
+            if back {
+                result = buffer.pop().is_some();
+            } else if let Some(c) = typed {
+                //leave room for the cursor
+                if (buffer.chars().count() as i32) < rect.w - 3 {
+                    buffer.push(c);
+                    result = true;
+                }
+            }
+
+            if result {
+                *text = buffer.clone();
+            }
+
+            format!("{}_", buffer)
+        }
+        _ => text.clone(),
+    };
+
+    let shown = format!("[{:<width$}]", shown_text, width = (rect.w - 2) as usize);
+
+    if context.hot == id || focused {
+        let foreground = (platform.get_foreground)();
+        (platform.set_foreground)(HIGHLIGHT_COLOR);
+        (platform.print_xy)(rect.x, rect.y, &shown);
+        (platform.set_foreground)(foreground);
+    } else {
+        (platform.print_xy)(rect.x, rect.y, &shown);
+    }
+
+    result
+}
+
 fn has_card(hand: &Hand, suit: Suit, value: Value) -> bool {
     for card in hand.iter() {
         if card.suit == suit && card.value == value {
@@ -2023,75 +4997,6 @@ fn has_card(hand: &Hand, suit: Suit, value: Value) -> bool {
     false
 }
 
-fn pairs_from_subsuit(subsuit: SubSuit) -> Vec<(Suit, Value)> {
-    match subsuit {
-        LowClubs => {
-            vec![(Clubs, Two),
-                 (Clubs, Three),
-                 (Clubs, Four),
-                 (Clubs, Five),
-                 (Clubs, Six),
-                 (Clubs, Seven)]
-        }
-        HighClubs => {
-            vec![(Clubs, Nine),
-                 (Clubs, Ten),
-                 (Clubs, Jack),
-                 (Clubs, Queen),
-                 (Clubs, King),
-                 (Clubs, Ace)]
-        }
-        LowDiamonds => {
-            vec![(Diamonds, Two),
-                 (Diamonds, Three),
-                 (Diamonds, Four),
-                 (Diamonds, Five),
-                 (Diamonds, Six),
-                 (Diamonds, Seven)]
-        }
-        HighDiamonds => {
-            vec![(Diamonds, Nine),
-                 (Diamonds, Ten),
-                 (Diamonds, Jack),
-                 (Diamonds, Queen),
-                 (Diamonds, King),
-                 (Diamonds, Ace)]
-        }
-        LowHearts => {
-            vec![(Hearts, Two),
-                 (Hearts, Three),
-                 (Hearts, Four),
-                 (Hearts, Five),
-                 (Hearts, Six),
-                 (Hearts, Seven)]
-        }
-        HighHearts => {
-            vec![(Hearts, Nine),
-                 (Hearts, Ten),
-                 (Hearts, Jack),
-                 (Hearts, Queen),
-                 (Hearts, King),
-                 (Hearts, Ace)]
-        }
-        LowSpades => {
-            vec![(Spades, Two),
-                 (Spades, Three),
-                 (Spades, Four),
-                 (Spades, Five),
-                 (Spades, Six),
-                 (Spades, Seven)]
-        }
-        HighSpades => {
-            vec![(Spades, Nine),
-                 (Spades, Ten),
-                 (Spades, Jack),
-                 (Spades, Queen),
-                 (Spades, King),
-                 (Spades, Ace)]
-        }
-    }
-}
-
 //calling this once will swallow multiple clicks on the button. We could either
 //pass in and return the number of clicks to fix that, or this could simply be
 //called multiple times per frame (once for each click).
@@ -2123,6 +5028,11 @@ fn do_button(platform: &Platform,
         context.set_next_hot(id);
     }
 
+    context.set_focusable(id);
+    if context.is_focused(id) && context.keyboard.activate {
+        result = true;
+    }
+
     if context.active == id && (platform.key_pressed)(KeyCode::MouseLeft) {
         draw_rect_with(platform,
                        spec.x,
@@ -2130,7 +5040,7 @@ fn do_button(platform: &Platform,
                        spec.w,
                        spec.h,
                        ["╔", "═", "╕", "║", "│", "╙", "─", "┘"]);
-    } else if context.hot == id {
+    } else if context.hot == id || context.is_focused(id) {
         draw_rect_with(platform,
                        spec.x,
                        spec.y,
@@ -2141,27 +5051,7 @@ fn do_button(platform: &Platform,
         draw_rect(platform, spec.x, spec.y, spec.w, spec.h);
     }
 
-    let text = &spec.text;
-
-    let x_ = if let Some(given_x) = None {
-        given_x
-    } else {
-        let rect_middle = spec.x + (spec.w / 2);
-
-        rect_middle - (text.len() as f32 / 2.0) as i32
-    };
-
-    let y_ = if let Some(given_y) = None {
-        given_y
-    } else {
-        spec.y + (spec.h / 2)
-
-    };
-
-    (platform.print_xy)(x_, y_, &text);
-    //No idea why the above works but this commented out bit doesn't.
-    //I guess the AsRef<SpecRect> impl is broken?
-    //print_centered_line(platform, spec, &spec.text);
+    print_centered_line(platform, &spec.rect(), &spec.text);
 
     return result;
 }
@@ -2194,7 +5084,7 @@ fn print_line_in_rect<T: AsRef<SpecRect>>(platform: &Platform,
     } else {
         let rect_middle = rect.x + (rect.w / 2);
 
-        rect_middle - (text.len() as f32 / 2.0) as i32
+        rect_middle - (text.chars().count() as f32 / 2.0) as i32
     };
 
     let y_ = if let Some(given_y) = y {
@@ -2213,6 +5103,30 @@ pub fn inside_rect(point: Point, x: i32, y: i32, w: i32, h: i32) -> bool {
     x <= point.x && y <= point.y && point.x < x + w && point.y < y + h
 }
 
+fn number_from_key(key: KeyCode) -> Option<u8> {
+    match key {
+        KeyCode::Row1 | KeyCode::Num1 => Some(1),
+        KeyCode::Row2 | KeyCode::Num2 => Some(2),
+        KeyCode::Row3 | KeyCode::Num3 => Some(3),
+        KeyCode::Row4 | KeyCode::Num4 => Some(4),
+        KeyCode::Row5 | KeyCode::Num5 => Some(5),
+        KeyCode::Row6 | KeyCode::Num6 => Some(6),
+        KeyCode::Row7 | KeyCode::Num7 => Some(7),
+        KeyCode::Row8 | KeyCode::Num8 => Some(8),
+        KeyCode::Row9 | KeyCode::Num9 => Some(9),
+        _ => None,
+    }
+}
+
+//shows the number key that can be used instead of clicking the button
+fn draw_shortcut_hint(platform: &Platform, spec: &ButtonSpec, number: u8) {
+    (platform.print_xy)(spec.x + 1, spec.y + 1, &number.to_string());
+}
+
+fn shortcut_pressed(context: &UIContext, number: u8) -> bool {
+    context.keyboard.number == Some(number)
+}
+
 const CARD_WIDTH: i32 = 16;
 const CARD_HEIGHT: i32 = 12;
 
@@ -2266,3 +5180,4 @@ fn draw_rect_with(platform: &Platform, x: i32, y: i32, w: i32, h: i32, edges: [&
     }
     (platform.print_xy)(right, bottom, edges[7]);
 }
+
@@ -0,0 +1,551 @@
+//Drives whole games through the same UI a player uses, drawing to the
+//headless platform, so the ask and declare flows can be checked without a
+//window. A test reads like instructions to a person at the table:
+//
+//    script.click("Ask for card");
+//    script.press(KeyCode::Enter);
+//    script.expect_text("Your team won");
+//
+//The games have no config directory unless a test gives them one, so the
+//player's own settings, saved game and records are never touched.
+
+use super::*;
+use common::headless;
+
+const WIDTH: i32 = 104;
+const HEIGHT: i32 = 36;
+
+pub struct Script {
+    platform: Platform,
+    pub state: State,
+}
+
+impl Script {
+    //a game already under way, skipping the title screen
+    pub fn new(settings: Settings) -> Script {
+        Script::sized(settings, WIDTH, HEIGHT)
+    }
+
+    //like `new`, in a window of some other size
+    pub fn sized(settings: Settings, width: i32, height: i32) -> Script {
+        let platform = headless::platform(width, height);
+        let state = new_game((platform.size)(), settings);
+
+        let mut script = Script { platform, state };
+        script.frame();
+
+        script
+    }
+
+    pub fn title_screen(settings: Settings) -> Script {
+        let platform = headless::platform(WIDTH, HEIGHT);
+        let mut state = new_game((platform.size)(), settings);
+        state.title_screen = true;
+
+        let mut script = Script { platform, state };
+        script.frame();
+
+        script
+    }
+
+    //draws one frame, handing over any events queued since the last one
+    pub fn frame(&mut self) {
+        let mut events = headless::take_events();
+
+        (self.platform.clear)(None);
+        update_and_render(&self.platform, &mut self.state, &mut events);
+    }
+
+    //clicks the button with exactly this text and draws the frame that shows
+    //what happened. Text buttons have no box, so they're found like any text.
+    pub fn click(&mut self, label: &str) {
+        match headless::find_boxed(label).or_else(|| headless::find(label)) {
+            Some(point) => self.click_at(point),
+            None => panic!("no button labelled {:?}\n{}", label, headless::screen_text()),
+        }
+    }
+
+    //ticks the box for one card and one teammate on the declare screen,
+    //where each teammate's name is centred over their column of boxes
+    pub fn choose(&mut self, card: &str, teammate: &str) {
+        let row = self.find(card);
+        let column = self.find(teammate);
+
+        self.click_at(Point::new(column.x + teammate.chars().count() as i32 / 2, row.y));
+    }
+
+    //a frame to hover, so the button is ready, a press, a release, then a
+    //frame to show what happened
+    pub fn click_at(&mut self, Point { x, y }: Point) {
+        headless::push_event(Event::MouseMove { x, y });
+        self.frame();
+
+        headless::push_event(Event::KeyPressed {
+                                 key: KeyCode::MouseLeft,
+                                 ctrl: false,
+                                 shift: false,
+                             });
+        self.frame();
+
+        headless::push_event(Event::KeyReleased {
+                                 key: KeyCode::MouseLeft,
+                                 ctrl: false,
+                                 shift: false,
+                             });
+        self.frame();
+        self.frame();
+    }
+
+    pub fn press(&mut self, key: KeyCode) {
+        headless::push_event(Event::KeyPressed {
+                                 key,
+                                 ctrl: false,
+                                 shift: false,
+                             });
+        headless::push_event(Event::KeyReleased {
+                                 key,
+                                 ctrl: false,
+                                 shift: false,
+                             });
+        self.frame();
+        self.frame();
+    }
+
+    pub fn has_text(&self, text: &str) -> bool {
+        headless::find(text).is_some()
+    }
+
+    pub fn expect_text(&self, text: &str) {
+        assert!(self.has_text(text),
+                "expected {:?} on screen\n{}",
+                text,
+                headless::screen_text());
+    }
+
+    pub fn expect_no_text(&self, text: &str) {
+        assert!(!self.has_text(text),
+                "did not expect {:?} on screen\n{}",
+                text,
+                headless::screen_text());
+    }
+
+    fn find(&self, text: &str) -> Point {
+        headless::find(text)
+            .unwrap_or_else(|| panic!("{:?} is not on screen\n{}", text, headless::screen_text()))
+    }
+}
+
+//every seat named, so tests don't depend on the random names
+fn test_settings() -> Settings {
+    Settings {
+        seed: Some(7),
+        first_player: Some(TeammatePlayer(ThePlayer)),
+        names: vec![(TeammatePlayer(ThePlayer), "You"),
+                    (TeammatePlayer(TeammateOne), "Tia"),
+                    (TeammatePlayer(TeammateTwo), "Ted"),
+                    (OpponentPlayer(OpponentZero), "Ann"),
+                    (OpponentPlayer(OpponentOne), "Bob"),
+                    (OpponentPlayer(OpponentTwo), "Cal")]
+            .into_iter()
+            .map(|(player, name)| (player, name.to_string()))
+            .collect(),
+        ..Settings::default()
+    }
+}
+
+#[test]
+fn new_game_on_the_title_screen_starts_a_game() {
+    let mut script = Script::title_screen(test_settings());
+
+    script.click("New Game");
+
+    assert!(!script.state.title_screen);
+    script.expect_no_text("New Game");
+    script.expect_text("Your turn");
+}
+
+//with seed 7 the deal is
+//  You  ♣2 4 ♦2 6 ♥4 6 ♠3 6
+//  Tia  ♣7 Q K ♦7 Q K ♠2 Q
+//  Ted  ♣3 9 10 A ♦9 A ♥A ♠7
+//  Ann  ♦4 10 J ♥3 9 ♠9 J A
+//  Bob  ♣5 6 J ♦3 ♥5 Q K ♠10
+//  Cal  ♦5 ♥2 7 10 J ♠4 5 K
+fn ask(script: &mut Script, opponent: &str, subsuit: &str, card: &str) {
+    script.click("Ask for card");
+    script.click(opponent);
+    script.click(subsuit);
+    script.click(card);
+}
+
+//takes the 5♣ and 6♣ from Bob, then correctly declares 2-7 of ♣ with Ted
+//holding the 3♣ and Tia the 7♣
+fn take_and_declare_low_clubs(script: &mut Script) {
+    ask(script, "Bob", "2-7 of ♣", "5 of ♣");
+    script.click("Aha!");
+    ask(script, "Bob", "2-7 of ♣", "6 of ♣");
+    script.click("Aha!");
+
+    script.click("Declare");
+    script.click("2-7 of ♣");
+    script.choose("3♣", "Ted");
+    script.choose("7♣", "Tia");
+    script.click("Submit");
+    script.click("Okay");
+}
+
+#[test]
+fn asking_for_a_card_the_opponent_has_takes_it() {
+    let mut script = Script::new(test_settings());
+
+    ask(&mut script, "Bob", "2-7 of ♣", "5 of ♣");
+
+    script.click("Aha!");
+
+    script.expect_text("Your turn");
+    script.expect_text("Bob           7 cards");
+    script.expect_text("You           9 cards");
+}
+
+#[test]
+fn asking_for_a_card_the_opponent_lacks_passes_the_turn() {
+    let mut script = Script::new(test_settings());
+
+    ask(&mut script, "Ann", "2-7 of ♣", "3 of ♣");
+
+    script.click("Oh...");
+
+    script.expect_text("Ann's turn");
+    script.expect_text("You           8 cards");
+}
+
+#[test]
+fn the_keyboard_can_start_an_ask() {
+    let mut script = Script::new(test_settings());
+
+    script.press(KeyCode::Tab);
+    script.press(KeyCode::Enter);
+
+    script.expect_text("← Back │ Ask");
+}
+
+#[test]
+fn declaring_a_half_suit_the_team_holds_scores_a_point() {
+    let mut script = Script::new(test_settings());
+
+    take_and_declare_low_clubs(&mut script);
+
+    script.expect_text("1:0");
+}
+
+#[test]
+fn declaring_a_half_suit_wrongly_gives_the_point_away() {
+    let mut script = Script::new(test_settings());
+
+    script.click("Declare");
+    script.click("2-7 of ♣");
+    script.click("Submit");
+
+    script.click("Okay");
+
+    script.expect_text("0:1");
+}
+
+#[test]
+fn declaring_the_last_half_suit_ends_the_game() {
+    let mut script = Script::new(test_settings());
+
+    //as if every other half-suit had already been declared
+    script.state.suits_in_play_bits = u8::from(LowClubs);
+    script.state.player_points = 4;
+    script.state.opponent_points = 3;
+    for (hand, _) in all_hands_mut(&mut script.state) {
+        hand.retain(|card| subsuit_from_pair(card.suit, card.value) == LowClubs);
+    }
+
+    take_and_declare_low_clubs(&mut script);
+
+    script.expect_text("Your team won");
+    script.expect_text("Final Score");
+    script.expect_text("5:3");
+}
+
+#[test]
+fn text_typed_into_a_field_is_dropped_when_focus_moves_on() {
+    let mut script = Script::title_screen(test_settings());
+    script.click("Settings");
+
+    let seed = script.find("Seed");
+    let seed_field = Point::new(seed.x + SETTINGS_LABEL_WIDTH + 1, seed.y);
+
+    script.click_at(seed_field);
+    script.press(KeyCode::X);
+    script.expect_text("[7x_");
+
+    //up to the button above, then back down
+    script.press(KeyCode::Up);
+    script.press(KeyCode::Down);
+
+    script.expect_text("[7_");
+    assert_eq!(script.state.settings.seed, Some(7));
+}
+
+#[test]
+fn the_game_goes_on_while_a_notebook_is_open() {
+    let mut script = Script::new(test_settings());
+    script.state = new_spectated_game(Size::new(WIDTH, HEIGHT),
+                                      Settings {
+                                          autoplay: AutoplaySpeed::Instant,
+                                          ..test_settings()
+                                      });
+
+    script.press(KeyCode::M);
+    script.expect_text("Tia's memory");
+    let moves = script.state.history.len();
+
+    for _ in 0..10 {
+        script.frame();
+    }
+
+    assert!(script.state.history.len() > moves);
+    script.expect_text("Tia's memory");
+}
+
+#[test]
+fn a_shown_hand_too_wide_for_its_seat_wraps() {
+    let mut script = Script::new(test_settings());
+    script.state = new_spectated_game(Size::new(WIDTH, HEIGHT), test_settings());
+    script.state.reveal_hands = true;
+
+    let bobs_cards = std::mem::take(&mut script.state.opponent_2);
+    script.state.opponent_1.extend(bobs_cards);
+    script.frame();
+
+    script.expect_text("Ann          16 cards");
+    script.expect_text("♣5 6 J ♦3 4 10 J ♥3 5");
+    script.expect_text("9 Q K ♠9 10 J A");
+}
+
+#[test]
+fn a_saved_game_comes_back_exactly_as_it_was() {
+    let mut script = Script::new(test_settings());
+
+    ask(&mut script, "Bob", "2-7 of ♣", "5 of ♣");
+    script.click("Aha!");
+    ask(&mut script, "Ann", "2-7 of ♣", "3 of ♣");
+    script.click("Oh...");
+
+    //Ann asks on the next frame, which should happen the same way in both games
+    let text = save_state(&script.state);
+    script.frame();
+    let screen = headless::screen_text();
+    let after = save_state(&script.state);
+
+    let mut loaded = Script::new(test_settings());
+    loaded.state = save_file::read(&text, new_game(Size::new(WIDTH, HEIGHT), test_settings()))
+        .unwrap();
+    loaded.frame();
+
+    assert_eq!(headless::screen_text(), screen);
+    assert_eq!(save_state(&loaded.state), after);
+}
+
+#[test]
+fn a_notice_is_shown_on_the_title_screen_until_a_new_game() {
+    let mut script = Script::title_screen(test_settings());
+    script.state.notice = Some("Reloaded with a new game".to_string());
+    script.frame();
+
+    script.expect_text("Reloaded with a new game");
+
+    script.click("New Game");
+
+    script.expect_no_text("Reloaded with a new game");
+}
+
+#[test]
+fn a_game_record_has_the_deal_and_every_move() {
+    let mut script = Script::new(test_settings());
+
+    take_and_declare_low_clubs(&mut script);
+
+    let text = game_record::write(&game_record(&script.state));
+
+    assert!(text.contains("\nYou: 2♣ 4♣ 2♦ 6♦ 4♥ 6♥ 3♠ 6♠\n"), "{}", text);
+    assert!(text.contains("\nOpponentOne: 5♣ 6♣ J♣ 3♦ 5♥ Q♥ K♥ 10♠\n"), "{}", text);
+    assert!(text.contains("\nYou asks OpponentOne for 6♣: yes\n"), "{}", text);
+    assert!(text.contains("\nYou declares 2-7 of ♣: 2♣ You, 3♣ TeammateTwo, 4♣ You, 5♣ You, \
+                           6♣ You, 7♣ TeammateOne: correct\n"),
+            "{}",
+            text);
+    assert!(text.contains("[TeammateTwo \"Ted\"]"), "{}", text);
+
+    assert_eq!(game_record::write(&game_record::parse(&text).unwrap()), text);
+}
+
+//the game from `take_and_declare_low_clubs`, as a record
+fn low_clubs_record() -> GameRecord {
+    let mut script = Script::new(test_settings());
+
+    take_and_declare_low_clubs(&mut script);
+
+    game_record(&script.state)
+}
+
+#[test]
+fn a_replay_steps_backwards_and_forwards_through_a_record() {
+    let mut script = Script::new(test_settings());
+    let size = (script.platform.size)();
+
+    start_replay(&mut script.state, size, low_clubs_record()).unwrap();
+    script.frame();
+
+    script.expect_text("Replay: move 0 of 3");
+    script.expect_text("You           8 cards");
+    script.expect_text("Bob           8 cards");
+
+    script.press(KeyCode::Right);
+
+    script.expect_text("Replay: move 1 of 3");
+    script.expect_text("You           9 cards");
+    script.expect_text("Bob           7 cards");
+    assert!(get_memory(&script.state, OpponentPlayer(OpponentOne))[&TeammatePlayer(ThePlayer)]
+                .model_hand
+                .contains(&Known(Suit::Clubs, Value::Five)));
+
+    script.press(KeyCode::End);
+
+    script.expect_text("Replay: move 3 of 3");
+    script.expect_text("1:0");
+
+    script.click("← Back");
+
+    script.expect_text("Replay: move 2 of 3");
+    script.expect_text("0:0");
+
+    script.press(KeyCode::Home);
+
+    script.expect_text("Replay: move 0 of 3");
+    script.expect_text("The deal");
+    assert!(get_memory(&script.state, OpponentPlayer(OpponentOne))[&TeammatePlayer(ThePlayer)]
+                .model_hand
+                .iter()
+                .all(|&card| card == Unknown));
+}
+
+#[test]
+fn a_record_that_contradicts_its_deal_is_refused() {
+    let mut script = Script::new(test_settings());
+    let size = (script.platform.size)();
+
+    let mut record = low_clubs_record();
+    record.deal
+        .get_mut(&OpponentPlayer(OpponentOne))
+        .unwrap()
+        .retain(|card| card.value != Value::Six);
+
+    let problem = start_replay(&mut script.state, size, record).err().unwrap();
+
+    assert_eq!(problem, "move 3: OpponentOne doesn't have the 6♣");
+    assert!(script.state.replay.is_none());
+}
+
+#[test]
+fn older_records_are_on_later_pages() {
+    let mut script = Script::title_screen(test_settings());
+
+    script.click("Replay a saved game");
+    let choices = (0..6).map(|i| (format!("{}.txt", i), format!("Record {}", i))).collect();
+    script.state.replay_choices = Some(choices);
+    script.frame();
+
+    script.expect_text("Replay a Game, page 1 of 2");
+    script.expect_text("Record 3");
+    script.expect_no_text("Record 4");
+    script.expect_no_text("← Newer");
+
+    script.click("Older →");
+
+    script.expect_text("Replay a Game, page 2 of 2");
+    script.expect_text("Record 5");
+    script.expect_no_text("Record 3");
+    script.expect_no_text("Older →");
+
+    script.click("← Newer");
+
+    script.expect_text("Record 0");
+}
+
+#[test]
+fn a_record_can_be_opened_by_its_path() {
+    let path = std::env::temp_dir().join(format!("canadian-fish-test-record-{}.txt",
+                                                 std::process::id()));
+    std::fs::write(&path, game_record::write(&low_clubs_record())).unwrap();
+
+    let size = Size::new(WIDTH, HEIGHT);
+    let mut script = Script::new(test_settings());
+    script.state = open_replay(new_game(size, test_settings()), size, &path.to_string_lossy());
+    script.frame();
+
+    script.expect_text("Replay: move 0 of 3");
+    script.expect_text("Bob           8 cards");
+
+    let _ = std::fs::remove_file(&path);
+
+    script.state = open_replay(new_game(size, test_settings()), size, &path.to_string_lossy());
+    script.frame();
+
+    script.expect_text("New Game");
+    script.expect_text("Could not read");
+}
+
+//the game is kept in a folder of this test's own rather than the config
+//directory
+#[test]
+fn quitting_saves_the_game_for_continue_until_it_is_over() {
+    let dir = std::env::temp_dir().join(format!("canadian-fish-test-{}", std::process::id()));
+
+    let mut script = Script::new(test_settings());
+    script.state.config_dir = Some(dir.clone());
+    ask(&mut script, "Bob", "2-7 of ♣", "5 of ♣");
+    script.click("Aha!");
+
+    script.press(KeyCode::Escape);
+    script.expect_text("Press Esc again to Quit");
+    script.press(KeyCode::Escape);
+
+    let saved = save_state(&script.state);
+
+    let mut script = Script::title_screen(test_settings());
+    script.state.config_dir = Some(dir.clone());
+    script.frame();
+    script.click("Continue");
+
+    script.expect_text("Your turn");
+    script.expect_text("You           9 cards");
+    assert_eq!(save_state(&script.state), saved);
+
+    script.state.suits_in_play_bits = 0;
+    script.press(KeyCode::Escape);
+    script.press(KeyCode::Escape);
+
+    let mut script = Script::title_screen(test_settings());
+    script.state.config_dir = Some(dir.clone());
+    script.frame();
+    script.expect_text("New Game");
+    script.expect_no_text("Continue");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn a_narrow_notebook_puts_each_half_suit_over_two_lines() {
+    let mut script = Script::sized(test_settings(), 72, HEIGHT);
+
+    script.click("Notebook");
+
+    script.expect_text("Has some        ║");
+    script.expect_text("║2-7 of ♣    2♣ ●··|···   3♣ ·??|???   4♣ ●··|···  !··|···         ║");
+    script.expect_text("║            5♣ ·??|???   6♣ ·??|???   7♣ ·??|???                  ║");
+    script.expect_text("║            Q♠ ·??|???   K♠ ·??|???   A♠ ·??|???                  ║");
+}